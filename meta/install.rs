@@ -1,65 +1,244 @@
 #!/usr/bin/env scriptr
 ---
+[package]
+edition = "2024"
+
 [dependencies]
-clap = { version = "4.5", features = ["derive"] }
+clap = { version = "4.5", features = ["derive", "env"] }
+clap_complete = "4.5"
+clap_complete_nushell = "4.5"
+clap_mangen = "0.2"
 colored = "2"
 anyhow = "1"
 dirs = "5"
+duct = "0.13"
+rayon = "1"
+serde = { version = "1", features = ["derive"] }
+serde_json = "1"
+blake3 = "1"
+toml = "0.8"
+log = "0.4"
+env_logger = "0.11"
+dialoguer = "0.11"
 ---
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
 use colored::*;
 use anyhow::{Result, Context, bail};
+use duct::cmd;
+use log::debug;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::os::unix::fs::symlink;
 use std::env;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by --json, so stdout carries nothing but the summary
+static PROGRESS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for progress output, which moves to stderr under --json
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if PROGRESS_TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 #[derive(Parser)]
 #[command(about = "Install scriptr scripts and shell completions")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    
     /// Specific scripts to install (installs all if none specified)
     scripts: Vec<String>,
     
-    /// Directory to symlink scripts into
-    #[arg(short, long, default_value = "~/bin")]
-    bin_dir: String,
+    /// Install the scripts listed under this name in [profiles] of the config file
+    #[arg(short, long, conflicts_with_all = ["scripts", "interactive"])]
+    profile: Option<String>,
     
-    /// Shell to set up completions for (fish, bash, zsh)
+    /// Directory to symlink scripts into [default: $XDG_BIN_HOME, or ~/.local/bin, or ~/bin]
+    #[arg(short, long, env = "PUBLIC_SCRIPTS_BIN_DIR")]
+    bin_dir: Option<String>,
+    
+    /// Shell to set up completions for (fish, bash, zsh, nu, pwsh)
     #[arg(short, long)]
     shell: Option<String>,
     
-    /// Force overwrite existing symlinks
+    /// Directory to write completions into, instead of the shell's usual one
+    #[arg(long, value_name = "PATH")]
+    completion_dir: Option<String>,
+    
+    /// Also install man pages for scripts that can generate them
+    #[arg(long)]
+    man: bool,
+    
+    /// Directory to write man pages into
+    #[arg(long, default_value = "~/.local/share/man/man1", value_name = "PATH")]
+    man_dir: String,
+    
+    /// Keep a .bak of a completion file before overwriting a different one
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set, value_name = "BOOL")]
+    backup: bool,
+    
+    /// Replace a regular file that sits where a script's symlink should go
     #[arg(short, long)]
     force: bool,
     
     /// List what would be installed without doing it
     #[arg(long)]
     dry_run: bool,
+    
+    /// Remove installed symlinks and completions instead of installing them
+    #[arg(long)]
+    uninstall: bool,
+    
+    /// Pull the latest changes into the repo before installing
+    #[arg(long, conflicts_with = "uninstall")]
+    update: bool,
+    
+    /// Copy scripts into the bin directory instead of symlinking them
+    #[arg(long)]
+    copy: bool,
+    
+    /// Create relative symlinks instead of absolute ones
+    #[arg(long, conflicts_with = "copy")]
+    relative: bool,
+    
+    /// Report drift from what the last install recorded, without changing anything
+    #[arg(long, conflicts_with_all = ["uninstall", "update"])]
+    status: bool,
+    
+    /// Remove completions this tool installed for scripts that are no longer in the repo
+    #[arg(long, conflicts_with_all = ["uninstall", "status", "write_checksums"])]
+    prune_completions: bool,
+    
+    /// Refuse to install unless every script matches the repo's checksums file
+    #[arg(long, conflicts_with = "uninstall")]
+    verify: bool,
+    
+    /// Regenerate the checksums file from the scripts as they are now
+    #[arg(long, conflicts_with_all = ["uninstall", "status", "verify"])]
+    write_checksums: bool,
+    
+    /// Pick which scripts to install from a checklist
+    #[arg(short, long, conflicts_with = "scripts")]
+    interactive: bool,
+    
+    /// Print the end-of-run summary as JSON
+    #[arg(long)]
+    json: bool,
+    
+    /// Log what the installer decides and why
+    #[arg(short, long, visible_alias = "debug")]
+    verbose: bool,
+    
+    /// Stop at the first script that fails instead of installing the rest
+    #[arg(long)]
+    strict: bool,
+    
+    /// Print a completion script for this shell (hidden flag)
+    #[arg(long, hide = true, value_name = "SHELL")]
+    generate_completions: Option<String>,
+    
+    /// Print a man page (hidden flag)
+    #[arg(long, hide = true)]
+    generate_man: bool,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Report the crates every script depends on and any version conflicts between them
+    Audit,
+    
+    /// Find symlinks in the bin directory that point at scripts no longer in the repo
+    Doctor {
+        /// Remove the broken symlinks it finds
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+/// Expands `~`, `~/path`, `~user` and `~user/path`, leaving the path alone if the home can't be found
 fn expand_tilde(path: &str) -> PathBuf {
-    if path.starts_with("~/") {
-        if let Some(home) = dirs::home_dir() {
-            home.join(&path[2..])
-        } else {
-            PathBuf::from(path)
-        }
+    let Some(rest) = path.strip_prefix('~') else {
+        return PathBuf::from(path);
+    };
+    let (user, tail) = rest.split_once('/').unwrap_or((rest, ""));
+    
+    let home = if user.is_empty() {
+        dirs::home_dir()
     } else {
-        PathBuf::from(path)
+        home_dir_for_user(user)
+    };
+    match home {
+        Some(home) if tail.is_empty() => home,
+        Some(home) => home.join(tail),
+        None => PathBuf::from(path),
     }
 }
 
+/// Another user's home directory, as listed in /etc/passwd
+fn home_dir_for_user(user: &str) -> Option<PathBuf> {
+    let passwd = fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 5 && fields[0] == user)
+        .map(|fields| PathBuf::from(fields[5]))
+}
+
+/// --bin-dir (or $PUBLIC_SCRIPTS_BIN_DIR), then $XDG_BIN_HOME, then whichever of ~/.local/bin and ~/bin exists
+fn resolve_bin_dir(bin_dir: Option<&str>) -> PathBuf {
+    if let Some(dir) = bin_dir {
+        return expand_tilde(dir);
+    }
+    if let Ok(dir) = env::var("XDG_BIN_HOME")
+        && !dir.is_empty()
+    {
+        return expand_tilde(&dir);
+    }
+    
+    let candidates = [expand_tilde("~/.local/bin"), expand_tilde("~/bin")];
+    candidates.iter()
+        .find(|dir| dir.is_dir())
+        .unwrap_or(&candidates[0])
+        .clone()
+}
+
 fn detect_shell() -> Option<String> {
     // First try SHELL environment variable
-    if let Ok(shell_path) = env::var("SHELL") {
-        if let Some(shell_name) = Path::new(&shell_path).file_name() {
-            return Some(shell_name.to_string_lossy().to_string());
+    if let Ok(shell_path) = env::var("SHELL")
+        && let Some(shell_name) = Path::new(&shell_path).file_stem()
+    {
+        let shell_name = shell_name.to_string_lossy().to_string();
+        // pwsh-preview and pwsh.exe are still PowerShell
+        if shell_name.starts_with("pwsh") || shell_name == "powershell" {
+            return Some("pwsh".to_string());
         }
+        return Some(shell_name);
     }
     None
 }
 
+/// A line that puts dir on PATH in this shell's own syntax, and what to do with it
+fn path_snippet(shell: Option<&str>, dir: &str) -> (String, &'static str) {
+    match shell {
+        // Universal variables persist on their own, so this is run once rather than added to config.fish
+        Some("fish") => (format!("set -Ux fish_user_paths {} $fish_user_paths", dir), "Run once in fish"),
+        Some("bash") => (format!("export PATH=\"{}:$PATH\"", dir), "Add to ~/.bashrc"),
+        Some("zsh") => (format!("export PATH=\"{}:$PATH\"", dir), "Add to ~/.zshrc"),
+        Some("nu") => (format!("$env.PATH = ($env.PATH | prepend '{}')", dir), "Add to your env.nu ($nu.env-path)"),
+        Some("pwsh" | "powershell") => (format!("$env:PATH = \"{}:$env:PATH\"", dir), "Add to your $PROFILE"),
+        _ => (format!("export PATH=\"{}:$PATH\"", dir), "Add to your shell configuration"),
+    }
+}
+
 fn get_shell_completion_dir(shell: &str) -> Result<Option<PathBuf>> {
     match shell {
         "fish" => {
@@ -88,39 +267,122 @@ fn get_shell_completion_dir(shell: &str) -> Result<Option<PathBuf>> {
                 bail!("Could not determine data directory for zsh")
             }
         }
+        "nu" => {
+            // Nushell doesn't autoload completions, config.nu has to source them
+            if let Some(config) = dirs::config_dir() {
+                Ok(Some(config.join("nushell").join("completions")))
+            } else {
+                bail!("Could not determine config directory for nushell")
+            }
+        }
+        "pwsh" | "powershell" => {
+            // PowerShell doesn't autoload completions, $PROFILE has to dot-source them
+            if let Some(home) = dirs::home_dir() {
+                Ok(Some(home.join(".config").join("powershell").join("completions")))
+            } else {
+                bail!("Could not determine home directory for PowerShell")
+            }
+        }
         _ => Ok(None),
     }
 }
 
-fn find_scripts(repo_dir: &Path, filter: Option<&[String]>) -> Result<Vec<PathBuf>> {
+fn resolve_completion_dir(shell: &str, override_dir: Option<&Path>) -> Result<Option<PathBuf>> {
+    match override_dir {
+        Some(dir) => Ok(Some(dir.to_path_buf())),
+        None => get_shell_completion_dir(shell),
+    }
+}
+
+fn completion_extension(shell: &str) -> &str {
+    match shell {
+        "pwsh" | "powershell" => "ps1",
+        _ => shell,
+    }
+}
+
+fn get_config_path() -> Option<PathBuf> {
+    let config_dir = env::var("XDG_CONFIG_HOME").ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+    Some(config_dir.join("public-scripts").join("config.toml"))
+}
+
+/// Script names for a profile, e.g. `dev = ["z"]` under `[profiles]`
+fn load_profile(name: &str) -> Result<Vec<String>> {
+    let path = get_config_path().context("Could not determine config directory")?;
+    let profiles = if path.exists() {
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: toml::Table = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        config.get("profiles").and_then(|p| p.as_table()).cloned().unwrap_or_default()
+    } else {
+        toml::Table::new()
+    };
+    
+    let Some(scripts) = profiles.get(name) else {
+        if profiles.is_empty() {
+            bail!("Unknown profile '{}', no profiles are defined in {}", name, path.display());
+        }
+        let available: Vec<&str> = profiles.keys().map(|k| k.as_str()).collect();
+        bail!("Unknown profile '{}'. Available profiles: {}", name, available.join(", "));
+    };
+    
+    let invalid = || format!("Profile '{}' in {} should be a list of script names", name, path.display());
+    scripts.as_array()
+        .with_context(invalid)?
+        .iter()
+        .map(|script| script.as_str().map(String::from).with_context(invalid))
+        .collect()
+}
+
+fn find_named_script(repo_dir: &Path, name: &str) -> Result<PathBuf> {
+    // Try with .rs extension first
+    let path_with_rs = repo_dir.join(format!("{}.rs", name));
+    let path_without_rs = repo_dir.join(name);
+    
+    let path = if path_with_rs.exists() {
+        path_with_rs
+    } else if path_without_rs.exists() && path_without_rs.extension().is_some_and(|e| e == "rs") {
+        path_without_rs
+    } else {
+        bail!("Script '{}' not found in {} (looked for {}.rs)", name, repo_dir.display(), name);
+    };
+    
+    if !path.is_file() {
+        bail!("'{}' is not a file", path.display());
+    }
+    // Check if it's executable
+    if let Ok(metadata) = fs::metadata(&path) {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            bail!("'{}' is not executable", path.display());
+        }
+    }
+    Ok(path)
+}
+
+/// Names that couldn't be used as scripts, and why
+type Skipped = Vec<(String, anyhow::Error)>;
+
+/// Scripts to work on, plus the ones that couldn't be used (unless strict, which bails on the first)
+fn find_scripts(repo_dir: &Path, filter: Option<&[String]>, strict: bool) -> Result<(Vec<PathBuf>, Skipped)> {
     let mut scripts = Vec::new();
+    let mut skipped = Vec::new();
     
     if let Some(names) = filter {
         // Find specific scripts by name (with or without .rs extension)
         for name in names {
-            // Try with .rs extension first
-            let path_with_rs = repo_dir.join(format!("{}.rs", name));
-            let path_without_rs = repo_dir.join(name);
-            
-            let path = if path_with_rs.exists() {
-                path_with_rs
-            } else if path_without_rs.exists() && path_without_rs.extension().map_or(false, |e| e == "rs") {
-                path_without_rs
-            } else {
-                bail!("Script '{}' not found in {} (looked for {}.rs)", name, repo_dir.display(), name);
-            };
-            
-            if !path.is_file() {
-                bail!("'{}' is not a file", path.display());
-            }
-            // Check if it's executable
-            if let Ok(metadata) = fs::metadata(&path) {
-                use std::os::unix::fs::PermissionsExt;
-                if metadata.permissions().mode() & 0o111 == 0 {
-                    bail!("'{}' is not executable", path.display());
+            match find_named_script(repo_dir, name) {
+                Ok(path) => {
+                    debug!("Resolved '{}' to {}", name, path.display());
+                    scripts.push(path);
                 }
+                Err(e) if strict => return Err(e),
+                Err(e) => skipped.push((name.clone(), e)),
             }
-            scripts.push(path);
         }
     } else {
         // Find all executable .rs scripts and also check meta/ subdirectory
@@ -128,150 +390,527 @@ fn find_scripts(repo_dir: &Path, filter: Option<&[String]>) -> Result<Vec<PathBu
         
         for dir in dirs_to_check {
             if dir.exists() {
+                debug!("Scanning {}", dir.display());
                 for entry in fs::read_dir(&dir)? {
                     let entry = entry?;
                     let path = entry.path();
                     
                     // Only include .rs files that are executable
-                    if path.is_file() && path.extension().map_or(false, |e| e == "rs") {
+                    if path.is_file() && path.extension().is_some_and(|e| e == "rs") {
                         // Check if it's executable
                         if let Ok(metadata) = fs::metadata(&path) {
                             use std::os::unix::fs::PermissionsExt;
                             if metadata.permissions().mode() & 0o111 != 0 {
+                                debug!("Found script {}", path.display());
                                 scripts.push(path);
+                            } else {
+                                debug!("Skipping {}: not executable", path.display());
                             }
                         }
+                    } else if path.is_file() {
+                        debug!("Skipping {}: not a .rs file", path.display());
                     }
                 }
+            } else {
+                debug!("Skipping {}: doesn't exist", dir.display());
             }
         }
-        scripts.sort();
     }
     
-    Ok(scripts)
+    // The same script can be reached twice, e.g. through a symlinked meta/ or two names for it
+    let mut seen = HashSet::new();
+    scripts.retain(|script| {
+        let first = seen.insert(script.canonicalize().unwrap_or_else(|_| script.clone()));
+        if !first {
+            debug!("Skipping {}: already found under another path", script.display());
+        }
+        first
+    });
+    scripts.sort();
+    
+    // A broken manifest would otherwise only show up as a confusing scriptr error at runtime
+    let mut valid = Vec::new();
+    for script in scripts {
+        match validate_front_matter(&script) {
+            Ok(()) => valid.push(script),
+            Err(e) if strict => return Err(e),
+            Err(e) => skipped.push((script_link_name(&script), e)),
+        }
+    }
+    
+    Ok((valid, skipped))
 }
 
 fn validate_existing_symlink(link_path: &Path, expected_target: &Path) -> Result<bool> {
-    if !link_path.exists() {
-        return Ok(true); // No conflict
-    }
-    
     if link_path.is_symlink() {
         let target = fs::read_link(link_path)?;
-        let canonical_target = if target.is_relative() {
-            link_path.parent().unwrap().join(&target).canonicalize()?
+        let target = if target.is_relative() {
+            link_path.parent().unwrap().join(&target)
         } else {
-            target.canonicalize()?
+            target
         };
-        let canonical_expected = expected_target.canonicalize()?;
         
-        Ok(canonical_target == canonical_expected)
-    } else {
+        match target.canonicalize() {
+            Ok(canonical_target) => Ok(canonical_target == expected_target.canonicalize()?),
+            // A broken symlink is stale, so it needs replacing rather than failing the install
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    } else if link_path.exists() {
         Ok(false) // It's a regular file, not a symlink
+    } else {
+        Ok(true) // No conflict
     }
 }
 
-fn install_script(script: &Path, bin_dir: &Path, force: bool, dry_run: bool) -> Result<()> {
+fn script_link_name(script: &Path) -> String {
     let script_name_full = script.file_name().unwrap().to_string_lossy();
     // Remove .rs extension for the symlink name
-    let link_name = if script_name_full.ends_with(".rs") {
-        &script_name_full[..script_name_full.len() - 3]
-    } else {
-        &script_name_full
+    script_name_full.strip_suffix(".rs")
+        .unwrap_or(&script_name_full)
+        .to_string()
+}
+
+/// The cargo manifest between the `---` fences at the top of a script, and its byte offset in the file
+fn front_matter(contents: &str) -> Option<(usize, &str)> {
+    let rest = match contents.strip_prefix("#!") {
+        Some(shebang) => shebang.split_once('\n')?.1,
+        None => contents,
+    };
+    // The opening fence may carry an infostring like `---cargo`
+    let rest = rest.strip_prefix("---")?.split_once('\n')?.1;
+    let end = rest.find("\n---")?;
+    Some((contents.len() - rest.len(), &rest[..end + 1]))
+}
+
+fn validate_front_matter(script: &Path) -> Result<()> {
+    let contents = fs::read_to_string(script)
+        .with_context(|| format!("Failed to read {}", script.display()))?;
+    let Some((offset, manifest)) = front_matter(&contents) else {
+        return Ok(());
     };
+    
+    if let Err(e) = toml::from_str::<toml::Table>(manifest) {
+        let error_offset = offset + e.span().map_or(0, |span| span.start);
+        let line = contents[..error_offset].matches('\n').count() + 1;
+        bail!("Invalid front-matter in {} at line {}: {}", script.display(), line, e.message());
+    }
+    Ok(())
+}
+
+/// A one-line description from a `# description:` line in the front-matter, or the clap `about`
+fn script_description(script: &Path) -> Option<String> {
+    let contents = fs::read_to_string(script).ok()?;
+    
+    if let Some((_, manifest)) = front_matter(&contents) {
+        for line in manifest.lines() {
+            if let Some(description) = line.trim().strip_prefix("# description:") {
+                return Some(description.trim().to_string());
+            }
+        }
+    }
+    
+    let about = &contents[contents.find("about = \"")? + "about = \"".len()..];
+    let end = about.find('"')?;
+    Some(about[..end].to_string())
+}
+
+fn select_scripts(scripts: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    use std::io::IsTerminal;
+    
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        bail!("--interactive needs a terminal. Pass the scripts to install by name instead.");
+    }
+    
+    let items: Vec<String> = scripts.iter()
+        .map(|script| match script_description(script) {
+            Some(description) => format!("{} — {}", script_link_name(script), description),
+            None => script_link_name(script),
+        })
+        .collect();
+    let defaults = vec![true; items.len()];
+    
+    let chosen = dialoguer::MultiSelect::new()
+        .with_prompt("Scripts to install (space to toggle, enter to confirm)")
+        .items(&items)
+        .defaults(&defaults)
+        .interact()
+        .context("Failed to read selection")?;
+    
+    Ok(chosen.into_iter().map(|i| scripts[i].clone()).collect())
+}
+
+/// An executable with this name that comes before bin_dir in $PATH, and so would win over ours
+fn shadowing_executable(name: &str, bin_dir: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    
+    let path_var = env::var("PATH").ok()?;
+    let bin_dir = bin_dir.canonicalize().unwrap_or_else(|_| bin_dir.to_path_buf());
+    for dir in env::split_paths(&path_var) {
+        if dir.canonicalize().unwrap_or_else(|_| dir.clone()) == bin_dir {
+            return None;
+        }
+        
+        let candidate = dir.join(name);
+        if let Ok(metadata) = fs::metadata(&candidate)
+            && metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+        {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Path to `target` as seen from inside `dir`, for two absolute paths
+fn relative_path(dir: &Path, target: &Path) -> PathBuf {
+    let dir: Vec<_> = dir.components().collect();
+    let target: Vec<_> = target.components().collect();
+    let common = dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    
+    let mut path = PathBuf::new();
+    for _ in common..dir.len() {
+        path.push("..");
+    }
+    for component in &target[common..] {
+        path.push(component);
+    }
+    path
+}
+
+/// What happened to one script, completion or man page
+#[derive(Clone, Copy)]
+enum Outcome {
+    Installed,
+    UpToDate,
+    Updated,
+}
+
+/// Counts for one kind of item in the end-of-run summary
+#[derive(Default, Serialize)]
+struct Tally {
+    installed: usize,
+    up_to_date: usize,
+    updated: usize,
+    failed: Vec<String>,
+}
+
+impl Tally {
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Installed => self.installed += 1,
+            Outcome::UpToDate => self.up_to_date += 1,
+            Outcome::Updated => self.updated += 1,
+        }
+    }
+    
+    fn describe(&self) -> String {
+        let mut parts = vec![
+            format!("{} new", self.installed).green().to_string(),
+            format!("{} up to date", self.up_to_date).dimmed().to_string(),
+            format!("{} updated", self.updated).yellow().to_string(),
+        ];
+        if !self.failed.is_empty() {
+            parts.push(format!("{} failed", self.failed.len()).red().to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+#[derive(Default, Serialize)]
+struct Summary {
+    dry_run: bool,
+    scripts: Tally,
+    completions: Tally,
+    man_pages: Tally,
+    /// Requested scripts that couldn't be used at all
+    skipped: Vec<String>,
+}
+
+impl Summary {
+    fn problems(&self) -> Vec<&str> {
+        self.skipped.iter()
+            .chain(&self.scripts.failed)
+            .chain(&self.completions.failed)
+            .chain(&self.man_pages.failed)
+            .map(|name| name.as_str())
+            .collect()
+    }
+    
+    fn print(&self, completions: bool, man_pages: bool) {
+        say!();
+        say!("{}", "📋 Summary".bold());
+        say!("   {} {}", "Scripts:    ".dimmed(), self.scripts.describe());
+        if completions {
+            say!("   {} {}", "Completions:".dimmed(), self.completions.describe());
+        }
+        if man_pages {
+            say!("   {} {}", "Man pages:  ".dimmed(), self.man_pages.describe());
+        }
+        if !self.skipped.is_empty() {
+            say!("   {} {}", "Skipped:    ".dimmed(), self.skipped.join(", ").red());
+        }
+        for name in self.scripts.failed.iter().chain(&self.completions.failed).chain(&self.man_pages.failed) {
+            say!("   {} {}", "✗".red().bold(), name);
+        }
+    }
+}
+
+/// Points link_path at target without a moment where link_path is missing from PATH
+fn replace_symlink(target: &Path, link_path: &Path) -> Result<()> {
+    // Renaming within one directory is atomic, so build the new link next to the old one
+    let temp_path = link_path.with_file_name(format!(".{}.{}.tmp", 
+        link_path.file_name().unwrap().to_string_lossy(), 
+        std::process::id()
+    ));
+    symlink(target, &temp_path)
+        .with_context(|| format!("Failed to create symlink from {} to {}", 
+            temp_path.display(), target.display()))?;
+    
+    if let Err(e) = fs::rename(&temp_path, link_path) {
+        debug!("Renaming over {} failed ({}), replacing it in two steps", link_path.display(), e);
+        let _ = fs::remove_file(&temp_path);
+        if link_path.is_symlink() {
+            fs::remove_file(link_path)?;
+        }
+        symlink(target, link_path)
+            .with_context(|| format!("Failed to create symlink from {} to {}", 
+                link_path.display(), target.display()))?;
+    }
+    Ok(())
+}
+
+fn install_script(script: &Path, bin_dir: &Path, force: bool, relative: bool, dry_run: bool) -> Result<(Outcome, String)> {
+    let link_name = script_link_name(script);
+    let link_name = link_name.as_str();
     let link_path = bin_dir.join(link_name);
+    let mut output = Vec::new();
     
     // Check what's at the target location
     if link_path.is_symlink() {
         // It's a symlink - validate it points to the right place
-        if let Ok(target) = fs::read_link(&link_path) {
-            let canonical_target = if target.is_relative() {
-                link_path.parent().unwrap().join(&target).canonicalize().ok()
-            } else {
-                target.canonicalize().ok()
-            };
-            let canonical_expected = script.canonicalize().ok();
-            
-            if canonical_target.is_some() && canonical_target == canonical_expected {
-                // Symlink is correct
-                println!("   {} {} {}", 
-                    "✓".green().dimmed(), 
-                    link_name.dimmed(),
-                    "(already installed)".dimmed()
-                );
-                return Ok(());
-            }
+        if validate_existing_symlink(&link_path, script)? {
+            // Symlink is correct
+            return Ok((Outcome::UpToDate, format!("   {} {} {}", 
+                "✓".green().dimmed(), 
+                link_name.dimmed(),
+                "(already installed)".dimmed()
+            )));
         }
         
         // Symlink is broken or points to wrong location, update it
-        if !dry_run {
-            fs::remove_file(&link_path)?;
-        }
-        println!("   {} {} {}", 
+        let reason = if link_path.exists() {
+            "(updating symlink)"
+        } else {
+            "(replacing broken symlink)"
+        };
+        output.push(format!("   {} {} {}", 
             "🔄".yellow(), 
             link_name.bold(),
-            "(updating symlink)".dimmed()
-        );
+            reason.dimmed()
+        ));
+    } else if force && link_path.is_file() {
+        output.push(format!("   {} {} {}", 
+            "🔄".yellow(), 
+            link_name.bold(),
+            "(replacing file, --force)".dimmed()
+        ));
     } else if link_path.exists() {
         // It's a regular file or directory - can't overwrite
         bail!("Regular file exists at {}. Cannot create symlink. Use --force to overwrite.", 
             link_path.display());
     }
     
-    // Create the symlink
+    // Create the symlink, always to the canonical script so it works wherever the installer ran from
     if !dry_run {
-        symlink(script, &link_path)
-            .with_context(|| format!("Failed to create symlink from {} to {}", 
-                link_path.display(), script.display()))?;
+        let script = script.canonicalize()
+            .with_context(|| format!("Failed to resolve {}", script.display()))?;
+        let target = if relative {
+            let link_dir = bin_dir.canonicalize().unwrap_or_else(|_| bin_dir.to_path_buf());
+            relative_path(&link_dir, &script)
+        } else {
+            script.clone()
+        };
+        debug!("Linking {} -> {}", link_path.display(), target.display());
+        replace_symlink(&target, &link_path)?;
     }
     
-    if !link_path.is_symlink() || dry_run {
-        println!("   {} {}", 
+    let outcome = if output.is_empty() { Outcome::Installed } else { Outcome::Updated };
+    if output.is_empty() || dry_run {
+        output.push(format!("   {} {}", 
             if dry_run { "→" } else { "✓" }.green().bold(), 
             link_name.bold()
-        );
+        ));
     }
     
-    Ok(())
+    Ok((outcome, output.join("\n")))
 }
 
-fn install_completion(completion_file: &Path, shell: &str, completion_dir: &Path, dry_run: bool) -> Result<()> {
-    let completion_name = completion_file.file_name().unwrap();
-    let target_path = completion_dir.join(completion_name);
+fn copy_script(script: &Path, bin_dir: &Path, dry_run: bool) -> Result<(Outcome, String)> {
+    use std::os::unix::fs::PermissionsExt;
     
-    // Check if already exists
-    if target_path.exists() {
+    let name = script_link_name(script);
+    let target_path = bin_dir.join(&name);
+    
+    let output = if target_path.is_symlink() {
+        // Switching over from a symlinked install
         if !dry_run {
-            // Compare contents to see if update needed
-            let source_content = fs::read_to_string(completion_file)?;
-            let target_content = fs::read_to_string(&target_path)?;
-            
-            if source_content == target_content {
-                // Extract script name for display
-                let script_name = completion_name.to_string_lossy()
-                    .trim_end_matches(&format!(".{}", shell))
-                    .to_string();
-                
-                println!("   {} {} {}", 
-                    "✓".green().dimmed(),
-                    script_name.dimmed(),
-                    "(already installed)".dimmed()
-                );
-                return Ok(());
-            }
-        } else {
-            // In dry-run mode, just report it exists
-            let script_name = completion_name.to_string_lossy()
-                .trim_end_matches(&format!(".{}", shell))
-                .to_string();
-            
-            println!("   {} {} {}", 
+            fs::remove_file(&target_path)?;
+        }
+        (Outcome::Updated, format!("   {} {} {}", 
+            "🔄".yellow(), 
+            name.bold(),
+            "(replacing symlink with a copy)".dimmed()
+        ))
+    } else if target_path.is_file() {
+        // Compare contents to see if update needed
+        let source_content = fs::read(script)?;
+        let target_content = fs::read(&target_path)?;
+        
+        if source_content == target_content {
+            return Ok((Outcome::UpToDate, format!("   {} {} {}", 
+                "✓".green().dimmed(), 
+                name.dimmed(),
+                "(already installed)".dimmed()
+            )));
+        }
+        (Outcome::Updated, format!("   {} {} {}", 
+            "🔄".yellow(), 
+            name.bold(),
+            "(updating copy)".dimmed()
+        ))
+    } else if target_path.exists() {
+        bail!("{} exists and is not a file. Cannot copy script there.", target_path.display());
+    } else {
+        (Outcome::Installed, format!("   {} {}", 
+            if dry_run { "→" } else { "✓" }.green().bold(), 
+            name.bold()
+        ))
+    };
+    
+    if !dry_run {
+        debug!("Copying {} to {}", script.display(), target_path.display());
+        fs::copy(script, &target_path)
+            .with_context(|| format!("Failed to copy {} to {}", script.display(), target_path.display()))?;
+        
+        let mut permissions = fs::metadata(&target_path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o755);
+        fs::set_permissions(&target_path, permissions)
+            .with_context(|| format!("Failed to make {} executable", target_path.display()))?;
+    }
+    
+    Ok(output)
+}
+
+/// A completion ready to install: which script it's for, its file name and its contents
+struct Completion {
+    script_name: String,
+    file_name: String,
+    content: String,
+}
+
+/// Whether the script's clap definition declares `field` as a `--long` flag, e.g.
+/// `generate_man: bool` under `#[arg(long, hide = true)]`. Mentioning the flag in a comment or
+/// string isn't enough, since the script would then be run with arguments it doesn't understand
+fn script_has_hook(script: &Path, field: &str) -> bool {
+    let Ok(source) = fs::read_to_string(script) else {
+        return false;
+    };
+    let lines: Vec<&str> = source.lines().map(str::trim).collect();
+    lines.iter().enumerate().any(|(i, line)| {
+        let is_field = line.strip_prefix("pub ").unwrap_or(line)
+            .strip_prefix(field)
+            .is_some_and(|rest| rest.trim_start().starts_with(':'));
+        // The attributes right above the field, looking past its doc comment
+        is_field && lines[..i].iter().rev()
+            .take_while(|line| line.starts_with("#[") || line.starts_with("//"))
+            .any(|line| line.starts_with("#[arg(") && line.contains("long"))
+    })
+}
+
+/// Whether `content` is plausibly a completion script for `shell`, rather than whatever else the script printed
+fn looks_like_completion(shell: &str, content: &str) -> bool {
+    match shell {
+        "bash" => content.contains("complete -F"),
+        "zsh" => content.starts_with("#compdef"),
+        "fish" => content.contains("complete -c"),
+        "elvish" => content.contains("edit:completion:arg-completer"),
+        "pwsh" | "powershell" => content.contains("Register-ArgumentCompleter"),
+        "nu" => content.contains("extern"),
+        _ => false,
+    }
+}
+
+/// Ask the script for completions generated from its clap definition
+fn generate_completion(script: &Path, shell: &str) -> Option<String> {
+    if !script_has_hook(script, "generate_completions") {
+        debug!("{} has no --generate-completions flag", script.display());
+        return None;
+    }
+    // clap_complete only knows PowerShell by its long name
+    let flag_shell = if shell == "pwsh" { "powershell" } else { shell };
+    // read() fails on a non-zero exit, so only a successful run counts
+    let content = cmd!(script, "--generate-completions", flag_shell)
+        .stdin_null()
+        .stderr_null()
+        .read()
+        .ok()?;
+    if !looks_like_completion(shell, &content) {
+        debug!("Ignoring {} output of {}: it doesn't look like a completion script", shell, script.display());
+        return None;
+    }
+    Some(content)
+}
+
+fn completion_for_script(script: &Path, repo_dir: &Path, shell: &str) -> Result<Option<Completion>> {
+    let script_name = script_link_name(script);
+    
+    // Generated completions can't drift from the code, so they win over committed files
+    if let Some(content) = generate_completion(script, shell) {
+        debug!("Generated {} completions for {}", shell, script_name);
+        let file_name = match shell {
+            // zsh only autoloads functions from files named after them
+            "zsh" => format!("_{}", script_name),
+            _ => format!("{}.{}", script_name, completion_extension(shell)),
+        };
+        return Ok(Some(Completion { script_name, file_name, content }));
+    }
+    
+    let committed = repo_dir.join("completions").join(format!("{}.{}", script_name, completion_extension(shell)));
+    if !committed.is_file() {
+        debug!("No {} completions for {}: it can't generate them and {} doesn't exist", shell, script_name, committed.display());
+        return Ok(None);
+    }
+    debug!("Using committed completions {}", committed.display());
+    let content = fs::read_to_string(&committed)
+        .with_context(|| format!("Failed to read {}", committed.display()))?;
+    let file_name = committed.file_name().unwrap().to_string_lossy().to_string();
+    Ok(Some(Completion { script_name, file_name, content }))
+}
+
+fn install_completion(completion: &Completion, completion_dir: &Path, backup: bool, dry_run: bool) -> Result<(Outcome, String)> {
+    let target_path = completion_dir.join(&completion.file_name);
+    let mut backup_path = None;
+    let outcome = if target_path.exists() { Outcome::Updated } else { Outcome::Installed };
+    
+    // Compare contents to see if update needed
+    if target_path.exists() {
+        let target_content = fs::read_to_string(&target_path)?;
+        if completion.content == target_content {
+            debug!("{} matches what's installed", target_path.display());
+            return Ok((Outcome::UpToDate, format!("   {} {} {}", 
                 "✓".green().dimmed(),
-                script_name.dimmed(),
+                completion.script_name.dimmed(),
                 "(already installed)".dimmed()
-            );
-            return Ok(());
+            )));
+        }
+        debug!("{} differs from the new completions", target_path.display());
+        if backup {
+            // zsh's compinit loads every _* file in $fpath, so the backup mustn't keep the underscore
+            let name = completion.file_name.trim_start_matches('_');
+            backup_path = Some(completion_dir.join(format!("{}.bak", name)));
         }
+    } else {
+        debug!("{} isn't installed yet", target_path.display());
     }
     
     if !dry_run {
@@ -279,153 +918,1092 @@ fn install_completion(completion_file: &Path, shell: &str, completion_dir: &Path
         fs::create_dir_all(completion_dir)
             .with_context(|| format!("Failed to create completion directory: {}", completion_dir.display()))?;
         
-        // Copy the completion file
-        fs::copy(completion_file, &target_path)
-            .with_context(|| format!("Failed to copy {} to {}", completion_file.display(), target_path.display()))?;
+        if let Some(backup_path) = &backup_path {
+            fs::copy(&target_path, backup_path)
+                .with_context(|| format!("Failed to back up {} to {}", target_path.display(), backup_path.display()))?;
+        }
+        fs::write(&target_path, &completion.content)
+            .with_context(|| format!("Failed to write {}", target_path.display()))?;
     }
     
-    // Extract script name from completion filename (e.g., "z.fish" -> "z")
-    let script_name = completion_name.to_string_lossy()
-        .trim_end_matches(&format!(".{}", shell))
-        .to_string();
-    
-    println!("   {} {}", 
+    let mut output = format!("   {} {}", 
         if dry_run { "→" } else { "✓" }.green().bold(),
-        script_name.bold()
+        completion.script_name.bold()
     );
-    
-    Ok(())
+    if let Some(backup_path) = backup_path {
+        let note = if dry_run { "previous version would be saved to" } else { "previous version saved to" };
+        output.push_str(&format!(" {}", format!("({} {})", note, backup_path.display()).dimmed()));
+    }
+    Ok((outcome, output))
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    
-    // Get repo directory (current working directory)
-    let repo_dir = env::current_dir()?;
+/// Man pages come from the same hook as completions, so only scripts that opt in get one
+fn generate_man_page(script: &Path) -> Option<String> {
+    if !script_has_hook(script, "generate_man") {
+        debug!("{} has no --generate-man flag", script.display());
+        return None;
+    }
+    let content = cmd!(script, "--generate-man")
+        .stdin_null()
+        .stderr_null()
+        .read()
+        .ok()?;
+    // Every roff page clap_mangen renders has a title header
+    if !content.lines().any(|line| line.starts_with(".TH ")) {
+        debug!("Ignoring --generate-man output of {}: it isn't a man page", script.display());
+        return None;
+    }
+    Some(content)
+}
+
+fn install_man_page(script: &Path, man_dir: &Path, dry_run: bool) -> Result<Option<(Outcome, String)>> {
+    let name = script_link_name(script);
+    let Some(content) = generate_man_page(script) else {
+        debug!("No man page for {}: it can't generate one", name);
+        return Ok(None);
+    };
+    let target_path = man_dir.join(format!("{}.1", name));
+    let outcome = if target_path.exists() { Outcome::Updated } else { Outcome::Installed };
     
-    // Expand and create bin directory
-    let bin_dir = expand_tilde(&args.bin_dir);
-    if !args.dry_run {
-        fs::create_dir_all(&bin_dir)
-            .context("Failed to create bin directory")?;
+    if fs::read_to_string(&target_path).is_ok_and(|existing| existing == content) {
+        return Ok(Some((Outcome::UpToDate, format!("   {} {} {}", 
+            "✓".green().dimmed(),
+            name.dimmed(),
+            "(already installed)".dimmed()
+        ))));
+    }
+    
+    if !dry_run {
+        fs::create_dir_all(man_dir)
+            .with_context(|| format!("Failed to create man directory: {}", man_dir.display()))?;
+        fs::write(&target_path, &content)
+            .with_context(|| format!("Failed to write {}", target_path.display()))?;
+    }
+    
+    Ok(Some((outcome, format!("   {} {}", 
+        if dry_run { "→" } else { "✓" }.green().bold(),
+        name.bold()
+    ))))
+}
+
+/// Everything the installer has put in place, so later runs can tell what drifted
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    scripts: Vec<ManifestScript>,
+    completions: Vec<ManifestCompletion>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestScript {
+    name: String,
+    link: PathBuf,
+    source: PathBuf,
+    /// blake3 of the script when it was installed
+    hash: String,
+    copied: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestCompletion {
+    script: String,
+    shell: String,
+    path: PathBuf,
+    hash: String,
+}
+
+impl Manifest {
+    /// Load the manifest, or an empty one if nothing has been installed yet
+    fn load() -> Result<Self> {
+        let Some(path) = get_manifest_path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()))
+    }
+    
+    fn save(&self) -> Result<()> {
+        let path = get_manifest_path()
+            .context("Could not determine state directory for the manifest")?;
+        fs::create_dir_all(path.parent().unwrap())
+            .with_context(|| format!("Failed to create {}", path.parent().unwrap().display()))?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+    
+    fn record_script(&mut self, script: &Path, link: PathBuf, copied: bool) -> Result<()> {
+        let hash = hash_file(script)?;
+        self.scripts.retain(|entry| entry.link != link);
+        self.scripts.push(ManifestScript {
+            name: script_link_name(script),
+            link,
+            source: script.to_path_buf(),
+            hash,
+            copied,
+        });
+        Ok(())
+    }
+    
+    fn record_completion(&mut self, completion: &Completion, shell: &str, path: PathBuf) {
+        self.completions.retain(|entry| entry.path != path);
+        self.completions.push(ManifestCompletion {
+            script: completion.script_name.clone(),
+            shell: shell.to_string(),
+            path,
+            hash: blake3::hash(completion.content.as_bytes()).to_hex().to_string(),
+        });
+    }
+    
+    /// Drop entries whose installed file is gone, e.g. after an uninstall
+    fn forget_missing(&mut self, link: &Path) {
+        if link.symlink_metadata().is_err() {
+            self.scripts.retain(|entry| entry.link != link);
+            self.completions.retain(|entry| entry.path != link);
+        }
+    }
+}
+
+fn get_manifest_path() -> Option<PathBuf> {
+    let state_dir = env::var("XDG_STATE_HOME").ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local").join("state")))?;
+    Some(state_dir.join("public-scripts").join("manifest.json"))
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let content = fs::read(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(blake3::hash(&content).to_hex().to_string())
+}
+
+fn script_drift(entry: &ManifestScript) -> Option<&'static str> {
+    if entry.link.symlink_metadata().is_err() {
+        return Some("(removed)");
+    }
+    if !entry.copied && !validate_existing_symlink(&entry.link, &entry.source).unwrap_or(false) {
+        return Some("(symlink no longer points at the repo)");
+    }
+    
+    let Ok(source_hash) = hash_file(&entry.source) else {
+        return Some("(source script missing)");
+    };
+    if source_hash != entry.hash {
+        return Some("(script changed since install)");
+    }
+    if entry.copied && hash_file(&entry.link).ok().as_deref() != Some(entry.hash.as_str()) {
+        return Some("(installed copy was edited)");
+    }
+    None
+}
+
+fn completion_drift(entry: &ManifestCompletion) -> Option<&'static str> {
+    match hash_file(&entry.path) {
+        Err(_) => Some("(removed)"),
+        Ok(hash) if hash != entry.hash => Some("(modified since install)"),
+        Ok(_) => None,
+    }
+}
+
+fn print_status() -> Result<()> {
+    let manifest = Manifest::load()?;
+    if manifest.scripts.is_empty() && manifest.completions.is_empty() {
+        say!("   {} Nothing has been installed yet", "ℹ️ ".dimmed());
+        return Ok(());
+    }
+    
+    say!("{} {}", 
+        "📦 Scripts".bold(), 
+        format!("({} recorded)", manifest.scripts.len()).dimmed()
+    );
+    for entry in &manifest.scripts {
+        match script_drift(entry) {
+            None => say!("   {} {} {}", 
+                "✓".green().dimmed(), 
+                entry.name.dimmed(),
+                entry.link.display().to_string().dimmed()
+            ),
+            Some(reason) => say!("   {} {} {}", "⚠️ ".yellow(), entry.name.bold(), reason.yellow()),
+        }
+    }
+    
+    if !manifest.completions.is_empty() {
+        say!();
+        say!("{} {}", 
+            "🐚 Completions".bold(), 
+            format!("({} recorded)", manifest.completions.len()).dimmed()
+        );
+        for entry in &manifest.completions {
+            let label = format!("{} ({})", entry.script, entry.shell);
+            match completion_drift(entry) {
+                None => say!("   {} {} {}", 
+                    "✓".green().dimmed(), 
+                    label.dimmed(),
+                    entry.path.display().to_string().dimmed()
+                ),
+                Some(reason) => say!("   {} {} {}", "⚠️ ".yellow(), label.bold(), reason.yellow()),
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Scripts are keyed by their path relative to the repo, like b3sum output
+fn checksum_key(script: &Path, repo_dir: &Path) -> String {
+    script.strip_prefix(repo_dir)
+        .unwrap_or(script)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn read_checksums(path: &Path) -> Result<BTreeMap<String, String>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    
+    let mut checksums = BTreeMap::new();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let Some((hash, name)) = line.split_once("  ") else {
+            bail!("Malformed line in {}: {}", path.display(), line);
+        };
+        checksums.insert(name.to_string(), hash.to_string());
+    }
+    Ok(checksums)
+}
+
+fn write_checksums(repo_dir: &Path, scripts: &[PathBuf], dry_run: bool) -> Result<()> {
+    let path = repo_dir.join("checksums");
+    // Keep entries for scripts that weren't selected this time
+    let mut checksums = if path.exists() {
+        read_checksums(&path)?
+    } else {
+        BTreeMap::new()
+    };
+    
+    say!("{} {}", 
+        "🔐 Checksums".bold(), 
+        path.display().to_string().dimmed()
+    );
+    for script in scripts {
+        let key = checksum_key(script, repo_dir);
+        let hash = hash_file(script)?;
+        if checksums.get(&key) == Some(&hash) {
+            say!("   {} {} {}", "✓".green().dimmed(), key.dimmed(), "(unchanged)".dimmed());
+        } else {
+            say!("   {} {}", if dry_run { "→" } else { "✓" }.green().bold(), key.bold());
+        }
+        checksums.insert(key, hash);
+    }
+    
+    if !dry_run {
+        let contents: String = checksums.iter()
+            .map(|(name, hash)| format!("{}  {}\n", hash, name))
+            .collect();
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn verify_scripts(repo_dir: &Path, scripts: &[PathBuf]) -> Result<()> {
+    let path = repo_dir.join("checksums");
+    if !path.exists() {
+        bail!("No checksums file at {}. Run with --write-checksums to create one.", path.display());
+    }
+    let checksums = read_checksums(&path)?;
+    
+    say!("{}", "🔐 Verifying checksums".bold());
+    let mut mismatches = Vec::new();
+    for script in scripts {
+        let key = checksum_key(script, repo_dir);
+        match checksums.get(&key) {
+            Some(expected) if *expected == hash_file(script)? => {
+                say!("   {} {}", "✓".green().dimmed(), key.dimmed());
+            }
+            Some(_) => {
+                say!("   {} {} {}", "✗".red().bold(), key.bold(), "(hash mismatch)".red());
+                mismatches.push(key);
+            }
+            None => {
+                say!("   {} {} {}", "✗".red().bold(), key.bold(), "(not in checksums)".red());
+                mismatches.push(key);
+            }
+        }
+    }
+    say!();
+    
+    if !mismatches.is_empty() {
+        bail!("Refusing to install, {} script(s) don't match {}: {}", 
+            mismatches.len(), path.display(), mismatches.join(", "));
+    }
+    Ok(())
+}
+
+/// Version requirement of a dependency entry, or where it comes from if it isn't from crates.io
+fn dependency_requirement(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(version) => version.clone(),
+        toml::Value::Table(table) => {
+            if let Some(version) = table.get("version").and_then(|v| v.as_str()) {
+                version.to_string()
+            } else if let Some(path) = table.get("path").and_then(|v| v.as_str()) {
+                format!("path {}", path)
+            } else if let Some(git) = table.get("git").and_then(|v| v.as_str()) {
+                format!("git {}", git)
+            } else {
+                "*".to_string()
+            }
+        }
+        other => other.to_string(),
+    }
+}
+
+fn audit_dependencies(repo_dir: &Path, scripts: &[PathBuf]) -> Result<()> {
+    // crate -> requirement -> scripts asking for it
+    let mut crates: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    
+    for script in scripts {
+        let name = checksum_key(script, repo_dir);
+        let contents = fs::read_to_string(script)
+            .with_context(|| format!("Failed to read {}", script.display()))?;
+        let Some((_, manifest)) = front_matter(&contents) else {
+            continue;
+        };
+        let manifest: toml::Table = toml::from_str(manifest)
+            .with_context(|| format!("Failed to parse the front-matter of {}", name))?;
+        
+        let Some(dependencies) = manifest.get("dependencies").and_then(|d| d.as_table()) else {
+            continue;
+        };
+        for (crate_name, value) in dependencies {
+            crates.entry(crate_name.clone())
+                .or_default()
+                .entry(dependency_requirement(value))
+                .or_default()
+                .push(name.clone());
+        }
+    }
+    
+    say!("{} {}", 
+        "🔍 Dependencies".bold(), 
+        format!("({} crates across {} scripts)", crates.len(), scripts.len()).dimmed()
+    );
+    
+    let mut conflicts = 0;
+    for (crate_name, requirements) in &crates {
+        if requirements.len() == 1 {
+            let (requirement, users) = requirements.iter().next().unwrap();
+            say!("   {} {} {} {}", 
+                "✓".green().dimmed(),
+                crate_name,
+                requirement.cyan(),
+                format!("({})", users.join(", ")).dimmed()
+            );
+            continue;
+        }
+        
+        conflicts += 1;
+        let versions: Vec<String> = requirements.iter()
+            .map(|(requirement, users)| format!("{} ({})", requirement, users.join(", ")))
+            .collect();
+        say!("   {} {} {}", "✗".red().bold(), crate_name.red().bold(), versions.join(", ").red());
+    }
+    
+    if conflicts > 0 {
+        say!();
+        bail!("{} crate(s) are required at different versions", conflicts);
+    }
+    Ok(())
+}
+
+fn prune_completions(repo_dir: &Path, shell: Option<&str>, dry_run: bool) -> Result<()> {
+    // Always compare against every script, not just the ones named on the command line
+    let (scripts, _) = find_scripts(repo_dir, None, false)?;
+    let names: HashSet<String> = scripts.iter().map(|script| script_link_name(script)).collect();
+    
+    let mut manifest = Manifest::load()?;
+    let stale: Vec<&ManifestCompletion> = manifest.completions.iter()
+        .filter(|entry| !names.contains(&entry.script))
+        .filter(|entry| shell.is_none_or(|shell| entry.shell == shell))
+        .collect();
+    
+    say!("{} {}", 
+        "🧹 Pruning completions".bold(), 
+        format!("({} for removed scripts)", stale.len()).dimmed()
+    );
+    
+    let mut forgotten = Vec::new();
+    for entry in stale {
+        let display_name = entry.path.display().to_string();
+        match hash_file(&entry.path) {
+            Err(_) => {
+                say!("   {} {} {}", "-".dimmed(), display_name.dimmed(), "(already gone)".dimmed());
+                forgotten.push(entry.path.clone());
+            }
+            // Edited since we wrote it, so it isn't ours to delete anymore
+            Ok(hash) if hash != entry.hash => {
+                say!("   {} {} {}", "-".dimmed(), display_name.dimmed(), "(modified, leaving it)".dimmed());
+            }
+            Ok(_) => {
+                if !dry_run {
+                    fs::remove_file(&entry.path)
+                        .with_context(|| format!("Failed to remove {}", entry.path.display()))?;
+                }
+                say!("   {} {}", 
+                    if dry_run { "→" } else { "✗" }.red().bold(),
+                    display_name.bold()
+                );
+                forgotten.push(entry.path.clone());
+            }
+        }
+    }
+    
+    if !dry_run {
+        manifest.completions.retain(|entry| !forgotten.contains(&entry.path));
+        manifest.save()?;
+    }
+    Ok(())
+}
+
+/// Where a symlink points, made absolute without requiring the target to exist
+fn symlink_target(link_path: &Path) -> Option<PathBuf> {
+    let target = fs::read_link(link_path).ok()?;
+    let target = link_path.parent()?.join(target);
+    // The script itself may be gone, but its directory usually isn't
+    match (target.parent()?.canonicalize(), target.file_name()) {
+        (Ok(dir), Some(name)) => Some(dir.join(name)),
+        _ => Some(target),
+    }
+}
+
+fn doctor(repo_dir: &Path, bin_dir: &Path, fix: bool, dry_run: bool) -> Result<()> {
+    let repo_dir = repo_dir.canonicalize()
+        .with_context(|| format!("Failed to resolve {}", repo_dir.display()))?;
+    
+    say!("{} {}", 
+        "🩺 Checking".bold(), 
+        bin_dir.display().to_string().cyan()
+    );
+    say!();
+    
+    let mut broken = Vec::new();
+    if bin_dir.is_dir() {
+        for entry in fs::read_dir(bin_dir)? {
+            let link_path = entry?.path();
+            if !link_path.is_symlink() || link_path.exists() {
+                continue;
+            }
+            let Some(target) = symlink_target(&link_path) else {
+                continue;
+            };
+            if target.starts_with(&repo_dir) {
+                broken.push((link_path, target));
+            }
+        }
+    }
+    broken.sort();
+    
+    if broken.is_empty() {
+        say!("   {} {}", "✓".green(), "No broken symlinks into this repo".dimmed());
+        return Ok(());
+    }
+    
+    let mut manifest = Manifest::load()?;
+    for (link_path, target) in &broken {
+        let name = link_path.file_name().unwrap().to_string_lossy().to_string();
+        if fix {
+            if !dry_run {
+                fs::remove_file(link_path)
+                    .with_context(|| format!("Failed to remove symlink {}", link_path.display()))?;
+                manifest.forget_missing(link_path);
+            }
+            say!("   {} {} {}", 
+                if dry_run { "→" } else { "✗" }.red().bold(),
+                name.bold(),
+                format!("(pointed at {})", target.display()).dimmed()
+            );
+        } else {
+            say!("   {} {} {}", 
+                "⚠️ ".yellow(),
+                name.bold(),
+                format!("→ {} (missing)", target.display()).yellow()
+            );
+        }
+    }
+    
+    if !fix {
+        say!();
+        say!("   {} Run {} to remove them", "💡".yellow(), "./meta/install.rs doctor --fix".cyan());
+    } else if !dry_run {
+        manifest.save()?;
+    }
+    Ok(())
+}
+
+fn git_head(repo_dir: &Path) -> Result<String> {
+    cmd!("git", "-C", repo_dir, "rev-parse", "HEAD")
+        .stderr_null()
+        .read()
+        .context("Failed to read the current git revision")
+}
+
+fn update_repo(repo_dir: &Path, dry_run: bool) -> Result<()> {
+    let is_work_tree = cmd!("git", "-C", repo_dir, "rev-parse", "--is-inside-work-tree")
+        .stderr_null()
+        .read()
+        .is_ok_and(|output| output.trim() == "true");
+    if !is_work_tree {
+        bail!("{} is not a git working tree, cannot update", repo_dir.display());
+    }
+    
+    say!("{} {}", "⬇️  Updating".bold(), repo_dir.display().to_string().cyan());
+    if dry_run {
+        say!("   {} {}", "→".green().bold(), "git pull".dimmed());
+        say!();
+        return Ok(());
+    }
+    
+    let before = git_head(repo_dir)?;
+    // Nothing has been touched yet, so a failed pull leaves the install as it was
+    cmd!("git", "-C", repo_dir, "pull", "--ff-only")
+        .stdout_null()
+        .run()
+        .context("git pull failed, existing symlinks left untouched")?;
+    let after = git_head(repo_dir)?;
+    
+    if before == after {
+        say!("   {} {}", "✓".green().dimmed(), "Already up to date".dimmed());
+    } else {
+        let changed = cmd!("git", "-C", repo_dir, "diff", "--name-status", format!("{}..{}", before, after))
+            .read()
+            .unwrap_or_default();
+        for line in changed.lines() {
+            say!("   {}", line.dimmed());
+        }
+    }
+    say!();
+    
+    Ok(())
+}
+
+fn uninstall_script(script: &Path, bin_dir: &Path, dry_run: bool) -> Result<()> {
+    let link_name = script_link_name(script);
+    let link_path = bin_dir.join(&link_name);
+    
+    // Only ever remove symlinks that point back at this script, or identical copies of it
+    let is_ours = if link_path.is_symlink() {
+        link_path.exists() && validate_existing_symlink(&link_path, script)?
+    } else {
+        link_path.is_file() && fs::read(&link_path)? == fs::read(script)?
+    };
+    
+    if !is_ours {
+        let reason = if link_path.is_symlink() || link_path.exists() {
+            "(not linked to this repo, leaving it)"
+        } else {
+            "(not installed)"
+        };
+        say!("   {} {} {}", "-".dimmed(), link_name.dimmed(), reason.dimmed());
+        return Ok(());
+    }
+    
+    if !dry_run {
+        fs::remove_file(&link_path)
+            .with_context(|| format!("Failed to remove symlink {}", link_path.display()))?;
+    }
+    
+    say!("   {} {}", 
+        if dry_run { "→" } else { "✗" }.red().bold(), 
+        link_name.bold()
+    );
+    
+    Ok(())
+}
+
+/// `expected_hash` is what we installed, so a file that hashes differently was edited or written by something else
+fn uninstall_completion(target_path: &Path, expected_hash: &str, dry_run: bool) -> Result<()> {
+    let display_name = target_path.file_name().unwrap_or_default().to_string_lossy();
+    
+    if !target_path.exists() {
+        say!("   {} {} {}", "-".dimmed(), display_name.dimmed(), "(not installed)".dimmed());
+        return Ok(());
+    }
+    
+    if hash_file(target_path)? != expected_hash {
+        say!("   {} {} {}", "-".dimmed(), display_name.dimmed(), "(modified, leaving it)".dimmed());
+        return Ok(());
+    }
+    
+    if !dry_run {
+        fs::remove_file(target_path)
+            .with_context(|| format!("Failed to remove {}", target_path.display()))?;
+    }
+    
+    say!("   {} {}", 
+        if dry_run { "→" } else { "✗" }.red().bold(),
+        display_name.bold()
+    );
+    
+    Ok(())
+}
+
+fn uninstall(scripts: &[PathBuf], repo_dir: &Path, bin_dir: &Path, shell: Option<&str>, completion_dir: Option<&Path>, dry_run: bool) -> Result<()> {
+    say!("{} {}", 
+        "🗑️  Removing scripts".bold(), 
+        format!("({} found)", scripts.len()).dimmed()
+    );
+    say!("   {} {}", 
+        "From:".dimmed(),
+        bin_dir.display().to_string().cyan()
+    );
+    say!();
+    
+    let mut manifest = Manifest::load()?;
+    for script in scripts {
+        uninstall_script(script, bin_dir, dry_run)?;
+        manifest.forget_missing(&bin_dir.join(script_link_name(script)));
+    }
+    
+    if let Some(shell_name) = shell
+        && let Some(completion_dir) = resolve_completion_dir(shell_name, completion_dir)?
+    {
+        say!();
+        say!("{} {} {}", 
+            "🐚 Removing completions".bold(),
+            "for".dimmed(),
+            shell_name.cyan()
+        );
+        
+        for script in scripts {
+            // The manifest knows what was actually installed, even by an older version of the script
+            let name = script_link_name(script);
+            let recorded: Vec<(PathBuf, String)> = manifest.completions.iter()
+                .filter(|entry| entry.script == name && entry.shell == shell_name && entry.path.parent() == Some(completion_dir.as_path()))
+                .map(|entry| (entry.path.clone(), entry.hash.clone()))
+                .collect();
+            
+            if recorded.is_empty()
+                && let Some(completion) = completion_for_script(script, repo_dir, shell_name)?
+            {
+                let target_path = completion_dir.join(&completion.file_name);
+                let hash = blake3::hash(completion.content.as_bytes()).to_hex().to_string();
+                uninstall_completion(&target_path, &hash, dry_run)?;
+                manifest.forget_missing(&target_path);
+            }
+            for (target_path, hash) in recorded {
+                uninstall_completion(&target_path, &hash, dry_run)?;
+                manifest.forget_missing(&target_path);
+            }
+        }
+    }
+    
+    if !dry_run {
+        manifest.save()?;
+    }
+    
+    say!();
+    say!("{} {}", "✨", "Done!".green().bold());
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    
+    // Keep normal output clean, --verbose turns on the decision log (RUST_LOG still works too)
+    env_logger::Builder::new()
+        .filter_level(if args.verbose { log::LevelFilter::Debug } else { log::LevelFilter::Warn })
+        .parse_default_env()
+        .format_timestamp(None)
+        .init();
+    
+    if let Some(shell) = &args.generate_completions {
+        let mut command = Args::command();
+        let mut stdout = std::io::stdout();
+        if shell == "nu" {
+            clap_complete::generate(clap_complete_nushell::Nushell, &mut command, "install", &mut stdout);
+        } else {
+            let shell: clap_complete::Shell = shell.parse()
+                .map_err(|e| anyhow::anyhow!("Cannot generate completions: {}", e))?;
+            clap_complete::generate(shell, &mut command, "install", &mut stdout);
+        }
+        return Ok(());
+    }
+    if args.generate_man {
+        clap_mangen::Man::new(Args::command().name("install")).render(&mut std::io::stdout())?;
+        return Ok(());
+    }
+    
+    if args.status {
+        return print_status();
+    }
+    if args.json {
+        PROGRESS_TO_STDERR.store(true, Ordering::Relaxed);
+    }
+    
+    // Get repo directory (current working directory)
+    let repo_dir = env::current_dir()?;
+    
+    if let Some(Command::Audit) = args.command {
+        let (scripts, _) = find_scripts(&repo_dir, None, true)?;
+        return audit_dependencies(&repo_dir, &scripts);
+    }
+    
+    // Expand and create bin directory
+    let bin_dir = resolve_bin_dir(args.bin_dir.as_deref());
+    let completion_dir = args.completion_dir.as_deref().map(expand_tilde);
+    if let Some(Command::Doctor { fix }) = args.command {
+        return doctor(&repo_dir, &bin_dir, fix, args.dry_run);
+    }
+    if !args.dry_run && !args.uninstall && !args.write_checksums && !args.prune_completions {
+        fs::create_dir_all(&bin_dir)
+            .context("Failed to create bin directory")?;
     }
     
     // Determine shell
     let shell = args.shell.or_else(detect_shell);
     
+    // Pull first so newly added scripts are discovered below
+    if args.update {
+        update_repo(&repo_dir, args.dry_run)?;
+    }
+    
     // Find scripts (filtered or all)
-    let filter = if args.scripts.is_empty() {
+    let profile_scripts = match &args.profile {
+        Some(profile) => Some(load_profile(profile)?),
+        None => None,
+    };
+    let filter = if let Some(names) = &profile_scripts {
+        Some(names.as_slice())
+    } else if args.scripts.is_empty() {
         None
     } else {
         Some(args.scripts.as_slice())
     };
-    let scripts = find_scripts(&repo_dir, filter)?;
+    // Only a normal install knows how to report scripts it had to skip
+    let fail_fast = args.strict || args.uninstall || args.write_checksums;
+    let (mut scripts, skipped) = find_scripts(&repo_dir, filter, fail_fast)?;
+    if args.interactive {
+        scripts = select_scripts(scripts)?;
+    }
     
     if args.dry_run {
-        println!("{}", "──────────────────────────────────────".dimmed());
-        println!("{}", "DRY RUN MODE".yellow().bold());
-        println!("{}", "No changes will be made".yellow());
-        println!("{}", "──────────────────────────────────────".dimmed());
-        println!();
+        say!("{}", "──────────────────────────────────────".dimmed());
+        say!("{}", "DRY RUN MODE".yellow().bold());
+        say!("{}", "No changes will be made".yellow());
+        say!("{}", "──────────────────────────────────────".dimmed());
+        say!();
+    }
+    
+    if args.write_checksums {
+        return write_checksums(&repo_dir, &scripts, args.dry_run);
+    }
+    
+    if args.prune_completions {
+        return prune_completions(&repo_dir, shell.as_deref(), args.dry_run);
+    }
+    
+    if args.uninstall {
+        return uninstall(&scripts, &repo_dir, &bin_dir, shell.as_deref(), completion_dir.as_deref(), args.dry_run);
+    }
+    
+    if args.verify {
+        verify_scripts(&repo_dir, &scripts)?;
     }
     
     // Install scripts
-    println!("{} {}", 
+    say!("{} {}", 
         "📦 Scripts".bold(), 
         format!("({} found)", scripts.len()).dimmed()
     );
-    println!("   {} {}", 
+    say!("   {} {}", 
         "Target:".dimmed(),
         bin_dir.display().to_string().cyan()
     );
-    println!();
+    say!();
     
-    for script in &scripts {
-        install_script(script, &bin_dir, args.force, args.dry_run)?;
+    // Each script touches its own path, so they can all be installed at once
+    let results: Vec<Result<(Outcome, String)>> = scripts.par_iter()
+        .map(|script| if args.copy {
+            copy_script(script, &bin_dir, args.dry_run)
+        } else {
+            install_script(script, &bin_dir, args.force, args.relative, args.dry_run)
+        })
+        .collect();
+    
+    let mut manifest = Manifest::load()?;
+    let mut summary = Summary { dry_run: args.dry_run, ..Summary::default() };
+    for (name, e) in skipped {
+        say!("   {} {} {}", "✗".red().bold(), name.bold(), format!("{:#}", e).red());
+        summary.skipped.push(name);
+    }
+    
+    for (script, result) in scripts.iter().zip(results) {
+        match result {
+            Ok((outcome, output)) => {
+                match script_description(script) {
+                    Some(description) => say!("{} {}", output, format!("— {}", description).dimmed()),
+                    None => say!("{}", output),
+                }
+                if let Some(other) = shadowing_executable(&script_link_name(script), &bin_dir) {
+                    say!("     {} {} {}", 
+                        "⚠️ ".yellow(),
+                        "shadowed by".yellow(),
+                        other.display().to_string().yellow().bold()
+                    );
+                }
+                manifest.record_script(script, bin_dir.join(script_link_name(script)), args.copy)?;
+                summary.scripts.record(outcome);
+            }
+            Err(e) if args.strict => {
+                return Err(e.context(format!("Failed to install {}", script_link_name(script))));
+            }
+            Err(e) => {
+                say!("   {} {} {}", "✗".red().bold(), script_link_name(script).bold(), format!("{:#}", e).red());
+                summary.scripts.failed.push(script_link_name(script));
+            }
+        }
     }
     
     // Install completions if shell is specified
-    if let Some(shell_name) = shell {
-        println!();
-        println!("{} {} {}", 
+    if let Some(shell_name) = shell.clone() {
+        say!();
+        say!("{} {} {}", 
             "🐚 Completions".bold(),
             "for".dimmed(),
             shell_name.cyan()
         );
         
-        if let Some(completion_dir) = get_shell_completion_dir(&shell_name)? {
-            // Look for completion files
-            let completions_dir = repo_dir.join("completions");
-            if completions_dir.exists() {
-                let mut found_completions = false;
-                for entry in fs::read_dir(&completions_dir)? {
-                    let entry = entry?;
-                    let path = entry.path();
-                    
-                    // Match completion files for the specified shell
-                    if path.is_file() {
-                        let name = path.file_name().unwrap().to_string_lossy();
-                        if name.ends_with(&format!(".{}", shell_name)) {
-                            // Check if this completion is for a script we have (whether newly installed or not)
-                            let script_name = name.trim_end_matches(&format!(".{}", shell_name));
-                            let script_exists = scripts.iter().any(|s| {
-                                s.file_name()
-                                    .map(|n| {
-                                        let name_str = n.to_string_lossy();
-                                        // Match either exact name or name.rs
-                                        name_str == script_name || 
-                                        name_str == format!("{}.rs", script_name)
-                                    })
-                                    .unwrap_or(false)
-                            });
-                            
-                            if script_exists {
-                                install_completion(&path, &shell_name, &completion_dir, args.dry_run)?;
-                                found_completions = true;
-                            }
-                        }
+        if let Some(completion_dir) = resolve_completion_dir(&shell_name, completion_dir.as_deref())? {
+            say!("   {} {}", 
+                "Target:".dimmed(),
+                completion_dir.display().to_string().cyan()
+            );
+            say!();
+            
+            // Generating a completion runs the script, so this is where parallelism pays off
+            let results: Vec<Result<Option<(Completion, Outcome, String)>>> = scripts.par_iter()
+                .map(|script| {
+                    let Some(completion) = completion_for_script(script, &repo_dir, &shell_name)? else {
+                        return Ok(None);
+                    };
+                    let (outcome, output) = install_completion(&completion, &completion_dir, args.backup, args.dry_run)?;
+                    Ok(Some((completion, outcome, output)))
+                })
+                .collect();
+            
+            let mut found_completions = false;
+            for (script, result) in scripts.iter().zip(results) {
+                match result {
+                    Ok(Some((completion, outcome, output))) => {
+                        say!("{}", output);
+                        summary.completions.record(outcome);
+                        manifest.record_completion(&completion, &shell_name, completion_dir.join(&completion.file_name));
+                        found_completions = true;
+                    }
+                    Ok(None) => {}
+                    Err(e) if args.strict => {
+                        return Err(e.context(format!("Failed to install {} completion", script_link_name(script))));
+                    }
+                    Err(e) => {
+                        let name = format!("{} completion", script_link_name(script));
+                        say!("   {} {} {}", "✗".red().bold(), name.bold(), format!("{:#}", e).red());
+                        summary.completions.failed.push(name);
                     }
                 }
-                
-                if !found_completions && !scripts.is_empty() {
-                    println!("   {} No completions found for installed scripts", "ℹ️ ".dimmed());
-                }
+            }
+            
+            if !found_completions && !scripts.is_empty() {
+                say!("   {} No completions found for installed scripts", "ℹ️ ".dimmed());
             }
             
             if shell_name == "fish" && !args.dry_run {
-                println!();
-                println!("   {} Run {} to reload completions", 
+                say!();
+                say!("   {} Run {} to reload completions", 
                     "💡".yellow(),
                     "source ~/.config/fish/config.fish".cyan()
                 );
             }
+            if shell_name == "nu" && !args.dry_run {
+                say!();
+                say!("   {} Add {} to your config.nu for each script", 
+                    "💡".yellow(),
+                    format!("source {}/<script>.nu", completion_dir.display()).cyan()
+                );
+            }
+            if matches!(shell_name.as_str(), "pwsh" | "powershell") && !args.dry_run {
+                say!();
+                say!("   {} Add {} to your $PROFILE for each script", 
+                    "💡".yellow(),
+                    format!(". {}/<script>.ps1", completion_dir.display()).cyan()
+                );
+            }
         } else {
-            println!("   {} Unknown shell: {}", "⚠️ ".yellow(), shell_name);
+            say!("   {} Unknown shell: {}", "⚠️ ".yellow(), shell_name);
+        }
+    }
+    
+    if args.man {
+        let man_dir = expand_tilde(&args.man_dir);
+        say!();
+        say!("{}", "📖 Man pages".bold());
+        say!("   {} {}", 
+            "Target:".dimmed(),
+            man_dir.display().to_string().cyan()
+        );
+        say!();
+        
+        let results: Vec<Result<Option<(Outcome, String)>>> = scripts.par_iter()
+            .map(|script| install_man_page(script, &man_dir, args.dry_run))
+            .collect();
+        for (script, result) in scripts.iter().zip(results) {
+            match result {
+                Ok(Some((outcome, output))) => {
+                    say!("{}", output);
+                    summary.man_pages.record(outcome);
+                }
+                Ok(None) => {}
+                Err(e) if args.strict => {
+                    return Err(e.context(format!("Failed to install {} man page", script_link_name(script))));
+                }
+                Err(e) => {
+                    let name = format!("{} man page", script_link_name(script));
+                    say!("   {} {} {}", "✗".red().bold(), name.bold(), format!("{:#}", e).red());
+                    summary.man_pages.failed.push(name);
+                }
+            }
         }
     }
     
     if !args.dry_run {
-        println!();
-        println!("{}", "──────────────────────────────────────".dimmed());
+        manifest.save()?;
+        say!();
+        say!("{}", "──────────────────────────────────────".dimmed());
     }
-    println!();
-    println!("{} {}", "✨", "Done!".green().bold());
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        summary.print(shell.is_some(), args.man);
+    }
+    let problems = summary.problems();
+    if !problems.is_empty() {
+        bail!("Failed to install {}", problems.join(", "));
+    }
+    say!();
+    say!("{} {}", "✨", "Done!".green().bold());
     
     // Check if bin_dir is in PATH
     if let Ok(path_var) = env::var("PATH") {
         let bin_dir_str = bin_dir.to_string_lossy();
         if !path_var.split(':').any(|p| p == bin_dir_str) {
-            println!();
-            println!("{} {} {}", 
+            say!();
+            say!("{} {} {}", 
                 "⚠️ ".yellow(),
                 bin_dir_str.yellow(),
                 "is not in your PATH".dimmed()
             );
-            println!();
-            println!("   Add to your shell configuration:");
-            println!("   {}", format!("export PATH=\"{}:$PATH\"", bin_dir_str).cyan());
+            say!();
+            let (snippet, instruction) = path_snippet(shell.as_deref(), &bin_dir_str);
+            say!("   {}:", instruction);
+            say!("   {}", snippet.cyan());
         }
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn expands_tilde_to_home() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_tilde("~"), home);
+        assert_eq!(expand_tilde("~/x"), home.join("x"));
+        assert_eq!(expand_tilde("~/.local/bin"), home.join(".local/bin"));
+    }
+    
+    #[test]
+    fn expands_tilde_user_from_passwd() {
+        // root is in /etc/passwd on any unix this runs on
+        let root_home = home_dir_for_user("root").unwrap();
+        assert_eq!(expand_tilde("~root"), root_home);
+        assert_eq!(expand_tilde("~root/x"), root_home.join("x"));
+        
+        // Unknown users are left alone rather than guessed at
+        assert_eq!(expand_tilde("~no-such-user-here/x"), PathBuf::from("~no-such-user-here/x"));
+    }
+    
+    #[test]
+    fn leaves_other_paths_alone() {
+        assert_eq!(expand_tilde("/usr/local/bin"), PathBuf::from("/usr/local/bin"));
+        assert_eq!(expand_tilde("bin/~x"), PathBuf::from("bin/~x"));
+        assert_eq!(expand_tilde(""), PathBuf::from(""));
+    }
+    
+    /// A fresh directory under the system temp dir, removed again on drop
+    struct TempDir(PathBuf);
+    
+    impl TempDir {
+        fn new(label: &str) -> TempDir {
+            let dir = env::temp_dir().join(format!("install-test-{}-{}", label, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+    
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+    
+    fn write_script(path: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(path, "#!/usr/bin/env scriptr\n---\n[dependencies]\n---\nfn main() {}\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    
+    #[test]
+    fn finds_a_symlinked_script_once() {
+        let repo = TempDir::new("dedupe");
+        write_script(&repo.0.join("tool.rs"));
+        symlink("tool.rs", repo.0.join("alias.rs")).unwrap();
+        // meta/ pointing back at the repo reaches both names a second time
+        symlink(".", repo.0.join("meta")).unwrap();
+        
+        let (scripts, skipped) = find_scripts(&repo.0, None, true).unwrap();
+        assert_eq!(scripts.len(), 1, "{:?}", scripts);
+        assert!(skipped.is_empty());
+        
+        let names = ["tool".to_string(), "alias".to_string()];
+        let (scripts, _) = find_scripts(&repo.0, Some(&names), true).unwrap();
+        assert_eq!(scripts.len(), 1, "{:?}", scripts);
+    }
+    
+    #[test]
+    fn hooks_need_a_clap_declaration() {
+        let dir = TempDir::new("hooks");
+        let script = dir.0.join("tool.rs");
+        let has_hook = |source: &str| {
+            fs::write(&script, source).unwrap();
+            script_has_hook(&script, "generate_completions")
+        };
+        
+        assert!(has_hook("struct Args {\n    /// Print completions\n    #[arg(long, hide = true)]\n    generate_completions: Option<String>,\n}\n"));
+        assert!(has_hook("struct Args {\n    #[arg(long = \"generate-completions\")]\n    pub generate_completions: Option<String>,\n}\n"));
+        
+        // Mentions that clap never sees don't count
+        assert!(!has_hook("// Pass --generate-completions to get generate_completions: output\nfn main() {}\n"));
+        assert!(!has_hook("fn main() {\n    println!(\"generate_completions: not supported\");\n}\n"));
+        assert!(!has_hook("fn main() {\n    let generate_completions: bool = false;\n}\n"));
+        // A positional argument can't be passed as --generate-completions
+        assert!(!has_hook("struct Args {\n    #[arg(value_name = \"SHELL\")]\n    generate_completions: Option<String>,\n}\n"));
+    }
+}
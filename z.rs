@@ -1,66 +1,464 @@
 #!/usr/bin/env scriptr
 ---
+[package]
+version = "0.1.0"
+edition = "2024"
+
 [dependencies]
 duct = "0.13"
-clap = { version = "4.5", features = ["derive"] }
+clap = { version = "4.5", features = ["derive", "env"] }
+clap_complete = "4.5"
+clap_complete_nushell = "4.5"
+clap_mangen = "0.2"
 colored = "2"
 anyhow = "1"
 blake3 = "1"
 kdl = "4"
 rayon = "1"
+skim = "0.10"
+serde = { version = "1", features = ["derive"] }
+serde_json = "1"
+regex = "1"
+humantime = "2"
+ctrlc = "3"
+arboard = "3"
+terminal_size = "0.3"
 ---
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::*;
 use duct::cmd;
 use std::env;
 use std::collections::HashMap;
-use anyhow::{Result, Context, bail};
+use anyhow::{Result, Context, bail, anyhow};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use skim::prelude::{unbounded, ItemPreview, PreviewContext, Skim, SkimItem, SkimItemReceiver, SkimItemSender, SkimOptionsBuilder};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    /// Alphabetically by session name
+    Name,
+    /// Oldest session first
+    Created,
+    /// Most tabs first
+    Tabs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BackendKind {
+    Zellij,
+    Tmux,
+}
+
+/// `0.1.0 (abc1234)`, from Z_GIT_HASH at build time or the checkout the script lives in,
+/// otherwise just the crate version
+fn z_version() -> &'static str {
+    let version = env!("CARGO_PKG_VERSION");
+    let hash = match option_env!("Z_GIT_HASH") {
+        Some(hash) if !hash.is_empty() => Some(hash.to_string()),
+        // Asking git costs a process, so only when the version is actually being printed
+        _ if env::args().skip(1).any(|arg| arg == "--version" || arg == "-V") => {
+            cmd!("git", "-C", env!("CARGO_MANIFEST_DIR"), "rev-parse", "--short", "HEAD")
+                .stderr_null()
+                .read()
+                .ok()
+        }
+        _ => None,
+    };
+    match hash.map(|hash| hash.trim().to_string()).filter(|hash| !hash.is_empty()) {
+        Some(hash) => Box::leak(format!("{} ({})", version, &hash[..hash.len().min(7)]).into_boxed_str()),
+        None => version,
+    }
+}
 
 #[derive(Parser)]
-#[command(about = "Enhanced zellij session manager")]
+#[command(about = "Enhanced zellij session manager", version = z_version())]
 struct Args {
-    /// Session name or hash prefix to attach to
+    #[command(subcommand)]
+    command: Option<Command>,
+    
+    /// Session name or hash prefix to attach to (shortcut for `z attach`)
     session: Option<String>,
     
-    /// Create a new session
-    #[arg(short = 'n', long)]
+    /// Same as `z new` (hidden flag kept for old scripts)
+    #[arg(short = 'n', long, hide = true)]
     new: bool,
     
-    /// Kill/delete a session
-    #[arg(short = 'k', long)]
+    /// Same as `z kill` (hidden flag kept for old scripts)
+    #[arg(short = 'k', long, hide = true)]
     kill: bool,
     
-    /// List sessions (names only)
-    #[arg(short = 'l', long)]
+    /// Kill or delete without asking for confirmation
+    #[arg(short = 'f', long, global = true)]
+    force: bool,
+    
+    /// Same as `z ls` (hidden flag kept for old scripts)
+    #[arg(short = 'l', long, hide = true)]
     list: bool,
     
-    /// Rename a session (provide old and new names)
-    #[arg(short = 'r', long)]
+    /// Same as `z rename` (hidden flag kept for old scripts)
+    #[arg(short = 'r', long, hide = true)]
     rename: bool,
     
+    /// Attach to a session working in the current directory, creating one if needed
+    #[arg(long)]
+    here: bool,
+    
+    /// Attach to the previously used session (same as `z -`)
+    #[arg(long)]
+    last: bool,
+    
+    /// Detach from the session this terminal is inside
+    #[arg(long)]
+    detach: bool,
+    
+    /// Copy the full name of a session to the clipboard instead of attaching
+    #[arg(long, value_name = "SESSION")]
+    copy: Option<String>,
+    
+    /// Pin a session so it's always listed first
+    #[arg(long, value_name = "SESSION")]
+    pin: Option<String>,
+    
+    /// Unpin a previously pinned session
+    #[arg(long, value_name = "SESSION", conflicts_with = "pin")]
+    unpin: Option<String>,
+    
+    /// Resurrect an exited session in the background without prompting (with --yes, recreate it if that fails)
+    #[arg(long, value_name = "SESSION")]
+    resurrect: Option<String>,
+    
+    /// Permanently delete a session, killing it first if it's running (-f to skip the confirmation)
+    #[arg(long, value_name = "SESSION")]
+    delete: Option<String>,
+    
+    /// Layout template for the hidden --new flag
+    #[arg(long, requires = "new", hide = true)]
+    template: Option<String>,
+    
+    /// Layout file for the hidden --new flag
+    #[arg(long, requires = "new", conflicts_with = "template", hide = true)]
+    layout: Option<PathBuf>,
+    
+    /// Starting directory for the hidden --new flag
+    #[arg(long, value_name = "DIR", requires = "new", hide = true)]
+    cwd: Option<PathBuf>,
+    
+    /// List available layout templates
+    #[arg(long)]
+    list_templates: bool,
+    
+    /// Clone a session's layout into a new session (provide source and new names)
+    #[arg(long)]
+    clone: bool,
+    
+    /// Order of the session listing
+    #[arg(long, value_enum, global = true)]
+    sort: Option<SortKey>,
+    
+    /// Reverse the listing order
+    #[arg(long, global = true)]
+    reverse: bool,
+    
+    /// Maximum length of session hash prefixes (default 8)
+    #[arg(long, value_name = "N", global = true)]
+    hash_len: Option<usize>,
+    
+    /// Show each session's full hash instead of its shortest unique prefix
+    #[arg(long, global = true)]
+    full_hash: bool,
+    
+    /// Only show sessions whose name matches this regex
+    #[arg(long, value_name = "PATTERN", global = true)]
+    filter: Option<String>,
+    
     /// Include exited sessions
-    #[arg(short = 'x', long)]
+    #[arg(short = 'x', long, global = true)]
     include_exited: bool,
     
-    /// New name for rename operation (positional second argument)
+    /// Delete all exited sessions
+    #[arg(long)]
+    prune: bool,
+    
+    /// Delete exited sessions created longer ago than this (e.g. 30m, 2h, 7d)
+    #[arg(long, value_name = "AGE", value_parser = humantime::parse_duration)]
+    prune_older_than: Option<Duration>,
+    
+    /// Answer yes to every confirmation prompt
+    #[arg(short = 'y', long, global = true)]
+    yes: bool,
+    
+    /// Answer no to every confirmation prompt
+    #[arg(long, conflicts_with = "yes", global = true)]
+    no: bool,
+    
+    /// Show what would be deleted without deleting anything
+    #[arg(long)]
+    dry_run: bool,
+    
+    /// New name for --clone (positional second argument)
     new_name: Option<String>,
     
+    /// Output the session list as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    
+    /// Show the git branch each tab's directory is on
+    #[arg(long)]
+    git: bool,
+    
+    /// Plain text listing without color, emoji or box drawing, for screen readers and logs
+    #[arg(long, conflicts_with_all = ["json", "tree"], global = true)]
+    plain: bool,
+    
+    /// Print the number of sessions and nothing else
+    #[arg(long)]
+    count: bool,
+    
+    /// Print the session list instead of opening the interactive picker
+    #[arg(long)]
+    no_picker: bool,
+    
+    /// Show each session's tabs and panes as a tree (implies --no-picker)
+    #[arg(long, global = true)]
+    tree: bool,
+    
+    /// Keep re-rendering the session list until interrupted
+    #[arg(long)]
+    watch: bool,
+    
+    /// Seconds between refreshes in watch mode
+    #[arg(long, value_name = "SECS", default_value_t = 2, requires = "watch")]
+    interval: u64,
+    
+    /// Terminal multiplexer to manage sessions with
+    #[arg(long, value_enum, env = "Z_BACKEND", default_value_t = BackendKind::Zellij, global = true)]
+    backend: BackendKind,
+    
+    /// Maximum number of sessions to fetch layouts for at once (default: number of CPUs)
+    #[arg(short = 'j', long, value_name = "N", global = true)]
+    jobs: Option<usize>,
+    
+    /// Always fetch fresh layouts instead of using recently cached ones
+    #[arg(long, global = true)]
+    no_cache: bool,
+    
+    /// When to use colors in output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+    
+    /// Extra arguments passed verbatim to the multiplexer when attaching or creating sessions
+    #[arg(last = true, value_name = "ARGS")]
+    passthrough: Vec<String>,
+    
     /// Output completion options (hidden flag)
     #[arg(long, hide = true)]
     completions: bool,
+    
+    /// Print a completion script for this shell, used by the installer (hidden flag)
+    #[arg(long, hide = true, value_name = "SHELL")]
+    generate_completions: Option<String>,
+    
+    /// Print a man page, used by the installer (hidden flag)
+    #[arg(long, hide = true)]
+    generate_man: bool,
 }
 
-#[derive(Debug)]
+#[derive(Subcommand)]
+enum Command {
+    /// List session names
+    Ls,
+    
+    /// Create a new session (named after the current directory if no name is given)
+    New {
+        /// Name for the session
+        name: Option<String>,
+        
+        /// Layout template from ~/.config/z/templates to create the session with
+        #[arg(long)]
+        template: Option<String>,
+        
+        /// Layout file to create the session with, or - to read it from stdin
+        #[arg(long, conflicts_with = "template")]
+        layout: Option<PathBuf>,
+        
+        /// Directory for the session's first pane to start in (also names the session if no name is given)
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<PathBuf>,
+        
+        /// Extra arguments passed verbatim to the multiplexer
+        #[arg(last = true, value_name = "ARGS")]
+        passthrough: Vec<String>,
+    },
+    
+    /// Kill/delete a session
+    Kill {
+        /// Session name, hash prefix or index
+        session: String,
+    },
+    
+    /// Rename a session
+    Rename {
+        /// Session name, hash prefix or index
+        session: String,
+        
+        /// New name for the session
+        new_name: String,
+    },
+    
+    /// Attach to a session, `name:tab` to focus a tab, or `-` for the previous one
+    Attach {
+        /// Session name, hash prefix or index
+        session: String,
+        
+        /// Extra arguments passed verbatim to the multiplexer
+        #[arg(last = true, value_name = "ARGS")]
+        passthrough: Vec<String>,
+    },
+    
+    /// Create a new session whose pane runs a command
+    Run {
+        /// Command line to run, e.g. "npm run dev"
+        command: String,
+        
+        /// Name for the session (derived from the directory and command if not given)
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        
+        /// Create the session in the background instead of attaching to it
+        #[arg(short = 'd', long)]
+        detach: bool,
+    },
+    
+    /// Type text into the focused pane of a session
+    Send {
+        /// Session name, hash prefix or index
+        session: String,
+        
+        /// Text to write; \n, \t and \\ are expanded, so use "make\n" to press Enter
+        text: String,
+    },
+    
+    /// Type text into the focused pane of every live session
+    Broadcast {
+        /// Text to write, with the same escapes as `send`
+        text: String,
+        
+        /// Leave out the session this terminal is inside
+        #[arg(long)]
+        exclude_current: bool,
+    },
+    
+    /// Find sessions with a tab running a matching command
+    FindCmd {
+        /// Text to look for in pane commands
+        query: String,
+        
+        /// Match case exactly instead of ignoring it
+        #[arg(short = 's', long)]
+        case_sensitive: bool,
+    },
+    
+    /// Set a session's description and tags, kept across restarts
+    Annotate {
+        /// Session name, hash prefix or index
+        session: String,
+        
+        /// Short description shown after the session name
+        #[arg(short = 'd', long)]
+        description: Option<String>,
+        
+        /// Tag to attach, replacing any existing tags (repeatable)
+        #[arg(short = 't', long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        
+        /// Remove the description and tags
+        #[arg(long, conflicts_with_all = ["description", "tags"])]
+        clear: bool,
+    },
+    
+    /// Re-create the tabs of one session in another, splits included
+    Merge {
+        /// Session whose tabs are copied
+        source: String,
+        
+        /// Session that receives the tabs
+        dest: String,
+        
+        /// Kill the source session once its tabs are copied
+        #[arg(long)]
+        kill_source: bool,
+    },
+    
+    /// Attach to or create a session for the best zoxide match
+    #[command(name = "j")]
+    Jump {
+        /// Keywords passed to `zoxide query`
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    
+    /// Attach to the session working in a directory, or create one there named after it
+    Open {
+        /// Directory to open, e.g. ~/projects/api or .
+        dir: PathBuf,
+    },
+    
+    /// Find sessions with a tab working in or beneath a directory
+    FindCwd {
+        /// Directory to look under, or text to look for in pane directories
+        query: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
 struct SessionInfo {
     name: String,
     is_current: bool,
     is_exited: bool,
     hash_prefix: String,
+    #[serde(rename = "age_secs", serialize_with = "serialize_age")]
+    created: Option<Duration>,
+    #[serde(flatten)]
+    meta: SessionMeta,
+}
+
+/// User-provided annotations, stored next to the session rather than inside it
+#[derive(Debug, Clone, Default, Serialize)]
+struct SessionMeta {
+    description: Option<String>,
+    tags: Vec<String>,
+    pinned: bool,
+}
+
+fn serialize_age<S: serde::Serializer>(age: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    age.map(|age| age.as_secs()).serialize(serializer)
+}
+
+/// A session as reported by the backend, before `z` adds its own bookkeeping
+struct ListedSession {
+    name: String,
+    is_exited: bool,
+    /// Whether the backend itself marked this as the session we're inside
+    is_current: bool,
+    /// How long ago the session was created
+    created: Option<Duration>,
 }
 
 impl AsRef<SessionInfo> for SessionInfo {
@@ -69,448 +467,2336 @@ impl AsRef<SessionInfo> for SessionInfo {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct TabInfo {
     name: String,
+    /// Distinct command/cwd combinations, identical panes collapsed
+    panes: Vec<PaneInfo>,
+    pane_count: usize,
+    /// The tab that was active when the layout was captured
+    focused: bool,
+    /// Command running in the tab's focused pane, null if unknown
+    #[serde(default)]
     command: Option<String>,
+    /// Working directory of the tab's focused pane, or of the tab itself
+    #[serde(default)]
     cwd: Option<String>,
+    /// Git branch checked out in the tab's directory, only looked up with --git
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
 }
 
-fn get_current_session() -> Option<String> {
-    env::var("ZELLIJ_SESSION_NAME").ok()
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct PaneInfo {
+    command: Option<String>,
+    cwd: Option<String>,
+    /// Lives in the tab's `floating_panes` block
+    floating: bool,
 }
 
-fn get_zellij_version() -> Result<String> {
-    let output = cmd!("zellij", "--version")
-        .read()
-        .context("Failed to get zellij version")?;
-    
-    // Parse "zellij 0.42.2" to get "0.42.2"
-    let version = output
-        .trim()
-        .split_whitespace()
-        .nth(1)
-        .context("Failed to parse zellij version")?
-        .to_string();
-    
-    Ok(version)
+#[derive(Debug)]
+struct Config {
+    include_exited: bool,
+    sort: Option<SortKey>,
+    shell: Option<String>,
+    hash_len: usize,
+    auto_attach: bool,
+    assume_yes: bool,
+    assume_no: bool,
+    backend: BackendKind,
+    cache_layouts: bool,
+    /// How long to wait for a session to dump its layout before giving up on it
+    layout_timeout: Duration,
+    theme: Theme,
+    /// Arguments given after `--`, spliced into attach and create invocations
+    passthrough: Vec<String>,
 }
 
-fn get_zellij_cache_dir() -> Result<PathBuf> {
-    let version = get_zellij_version()?;
-    
-    let cache_base = if cfg!(target_os = "macos") {
-        let home = env::var("HOME").context("HOME not set")?;
-        PathBuf::from(home)
-            .join("Library")
-            .join("Caches")
-            .join("org.Zellij-Contributors.Zellij")
-            .join(&version)
-    } else {
-        // Linux and others
-        let home = env::var("HOME").context("HOME not set")?;
-        PathBuf::from(home)
-            .join(".cache")
-            .join("zellij")
-            .join(&version)
-    };
-    
-    Ok(cache_base)
+/// Colors for each part of the listing; `None` leaves the terminal's default color
+#[derive(Debug, Clone)]
+struct Theme {
+    prefix: Option<Color>,
+    name: Option<Color>,
+    current: Option<Color>,
+    command: Option<Color>,
+    cwd: Option<Color>,
+    tab: Option<Color>,
 }
 
-fn load_cached_session_layout(session_name: &str) -> Result<String> {
-    let cache_dir = get_zellij_cache_dir()?;
-    let layout_path = cache_dir
-        .join("session_info")
-        .join(session_name)
-        .join("session-layout.kdl");
-    
-    if layout_path.exists() {
-        fs::read_to_string(&layout_path)
-            .with_context(|| format!("Failed to read cached layout from {:?}", layout_path))
-    } else {
-        bail!("No cached layout found for session {}", session_name)
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            prefix: Some(Color::Yellow),
+            name: Some(Color::Cyan),
+            current: Some(Color::Green),
+            command: Some(Color::Blue),
+            cwd: None,
+            tab: None,
+        }
     }
 }
 
-fn compute_hash_prefix(name: &str) -> String {
-    let hash = blake3::hash(name.as_bytes());
-    hash.to_hex().chars().take(8).collect()
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// The configured theme, set once in `main` like the color override
+fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
 }
 
-fn find_shortest_prefixes<T: AsRef<SessionInfo>>(sessions: &[T]) -> HashMap<String, String> {
-    let mut prefixes = HashMap::new();
-    
-    for session in sessions {
-        let session = session.as_ref();
-        // Start with 1 character and increase until unique
-        for len in 1..=8 {
-            let prefix: String = session.hash_prefix.chars().take(len).collect();
-            let is_unique = sessions.iter()
-                .map(|s| s.as_ref())
-                .filter(|s| s.name != session.name)
-                .all(|s| !s.hash_prefix.starts_with(&prefix));
-            
-            if is_unique {
-                prefixes.insert(session.name.clone(), prefix);
-                break;
-            }
-        }
+fn paint(text: &str, color: Option<Color>) -> ColoredString {
+    match color {
+        Some(color) => text.color(color),
+        None => text.normal(),
     }
-    
-    prefixes
 }
 
-fn list_sessions(include_exited: bool) -> Result<Vec<SessionInfo>> {
-    let output = cmd!("zellij", "list-sessions")
-        .read()
-        .context("Failed to list zellij sessions")?;
-    
-    let current_session = get_current_session();
-    
-    let sessions: Vec<SessionInfo> = output
-        .lines()
-        .filter(|line| !line.trim().is_empty() && (include_exited || !line.contains("EXITED")))
-        .map(|line| {
-            let is_exited = line.contains("EXITED");
-            
-            // Extract session name from the colored output
-            let name = if let Some(start) = line.find('\x1b') {
-                if let Some(end_start) = line[start..].find("m") {
-                    let name_start = start + end_start + 1;
-                    if let Some(name_end) = line[name_start..].find('\x1b') {
-                        line[name_start..name_start + name_end].trim().to_string()
-                    } else {
-                        line.split_whitespace().next().unwrap_or("").to_string()
-                    }
-                } else {
-                    line.split_whitespace().next().unwrap_or("").to_string()
-                }
-            } else {
-                line.split_whitespace().next().unwrap_or("").to_string()
-            };
-            
-            let is_current = current_session.as_ref() == Some(&name);
-            let hash_prefix = compute_hash_prefix(&name);
-            SessionInfo { name, is_current, is_exited, hash_prefix }
-        })
-        .filter(|s| !s.name.is_empty())
-        .collect();
-    
-    Ok(sessions)
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            include_exited: false,
+            sort: None,
+            shell: None,
+            hash_len: 8,
+            auto_attach: false,
+            assume_yes: false,
+            assume_no: false,
+            backend: BackendKind::Zellij,
+            cache_layouts: true,
+            layout_timeout: Duration::from_secs(2),
+            theme: Theme::default(),
+            passthrough: Vec::new(),
+        }
+    }
 }
 
-fn get_layout_cwd(layout: &str) -> Option<String> {
-    // Parse KDL and extract the cwd from layout node
-    if let Ok(doc) = layout.parse::<kdl::KdlDocument>() {
-        if let Some(layout_node) = doc.nodes().iter().find(|n| n.name().value() == "layout") {
-            if let Some(cwd_entry) = layout_node.entries().iter().find(|e| e.name().map(|n| n.value()) == Some("cwd")) {
-                if let Some(cwd_val) = cwd_entry.value().as_string() {
-                    return Some(cwd_val.to_string());
-                }
-            }
+impl Config {
+    fn backend(&self) -> &'static dyn Backend {
+        match self.backend {
+            BackendKind::Zellij => &Zellij,
+            BackendKind::Tmux => &Tmux,
         }
     }
-    None
 }
 
-fn parse_kdl_layout(layout: &str) -> Result<Vec<TabInfo>> {
-    // Parse KDL
-    let doc = layout.parse::<kdl::KdlDocument>()
-        .context("Failed to parse KDL layout")?;
+fn get_config_dir() -> Option<PathBuf> {
+    if let Ok(config_home) = env::var("XDG_CONFIG_HOME")
+        && !config_home.is_empty()
+    {
+        return Some(PathBuf::from(config_home).join("z"));
+    }
+    let home = env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".config").join("z"))
+}
+
+fn get_config_path() -> Option<PathBuf> {
+    get_config_dir().map(|dir| dir.join("config.kdl"))
+}
+
+fn parse_config(contents: &str) -> Result<Config> {
+    let doc = contents.parse::<kdl::KdlDocument>()
+        .context("Failed to parse config")?;
     
-    let mut tabs = Vec::new();
+    let mut config = Config::default();
     
-    // Find the layout node first
-    if let Some(layout_node) = doc.nodes().iter().find(|n| n.name().value() == "layout") {
-        if let Some(layout_children) = layout_node.children() {
-            // Now find all tab nodes within the layout
-            for node in layout_children.nodes() {
-                if node.name().value() == "tab" {
-                    let mut tab_name = String::from("Tab");
-                    let mut panes_info: Vec<(Option<String>, Option<String>)> = Vec::new();
-                    
-                    // Get tab name if present
-                    if let Some(name_entry) = node.entries().iter().find(|e| e.name().map(|n| n.value()) == Some("name")) {
-                        if let Some(name_val) = name_entry.value().as_string() {
-                            tab_name = name_val.to_string();
-                        }
-                    }
-                    
-                    // Look through child nodes for panes
-                    if let Some(children) = node.children() {
-                        for child in children.nodes() {
-                            if child.name().value() == "pane" {
-                                let mut command = None;
-                                let mut cwd = None;
-                                
-                                // Get command attribute
-                                if let Some(cmd_entry) = child.entries().iter().find(|e| e.name().map(|n| n.value()) == Some("command")) {
-                                    if let Some(cmd_val) = cmd_entry.value().as_string() {
-                                        command = Some(cmd_val.to_string());
-                                    }
-                                }
-                                
-                                // Get cwd attribute
-                                if let Some(cwd_entry) = child.entries().iter().find(|e| e.name().map(|n| n.value()) == Some("cwd")) {
-                                    if let Some(cwd_val) = cwd_entry.value().as_string() {
-                                        cwd = Some(cwd_val.to_string());
-                                    }
-                                }
-                                
-                                // Only add if it's not a plugin pane
-                                if command.is_some() || cwd.is_some() {
-                                    panes_info.push((command, cwd));
-                                }
-                            }
-                        }
-                    }
-                    
-                    // If we found panes, add a tab entry for each unique combination
-                    if !panes_info.is_empty() {
-                        // Group by command/cwd and take the first of each unique combination
-                        let mut seen = std::collections::HashSet::new();
-                        for (command, cwd) in panes_info {
-                            let key = (command.clone(), cwd.clone());
-                            if seen.insert(key) {
-                                tabs.push(TabInfo {
-                                    name: tab_name.clone(),
-                                    command,
-                                    cwd,
-                                });
-                            }
-                        }
-                    } else {
-                        tabs.push(TabInfo {
-                            name: tab_name,
-                            command: None,
-                            cwd: None,
-                        });
-                    }
-                }
+    for node in doc.nodes() {
+        let key = node.name().value();
+        if key == "theme" {
+            if let Some(children) = node.children() {
+                parse_theme(children, &mut config.theme);
+            }
+            continue;
+        }
+        
+        // Every setting takes a single positional value, e.g. `include_exited true`
+        let value = match node.entries().iter().find(|e| e.name().is_none()) {
+            Some(entry) => entry.value(),
+            None => {
+                eprintln!("{}: Config setting '{}' has no value, ignoring", "Warning".yellow(), key);
+                continue;
+            }
+        };
+        
+        match (key, value) {
+            ("include_exited", v) if v.as_bool().is_some() => {
+                config.include_exited = v.as_bool().unwrap();
+            }
+            ("auto_attach", v) if v.as_bool().is_some() => {
+                config.auto_attach = v.as_bool().unwrap();
+            }
+            ("sort", v) if v.as_string().and_then(|s| SortKey::from_str(s, true).ok()).is_some() => {
+                config.sort = v.as_string().and_then(|s| SortKey::from_str(s, true).ok());
+            }
+            ("shell", v) if v.as_string().is_some() => {
+                config.shell = Some(v.as_string().unwrap().to_string());
+            }
+            ("hash_len", v) if v.as_i64().is_some_and(|n| n > 0) => {
+                config.hash_len = v.as_i64().unwrap() as usize;
+            }
+            ("layout_timeout", v) if v.as_i64().is_some_and(|n| n > 0) => {
+                config.layout_timeout = Duration::from_secs(v.as_i64().unwrap() as u64);
+            }
+            ("include_exited" | "auto_attach" | "sort" | "shell" | "hash_len" | "layout_timeout", v) => {
+                eprintln!("{}: Invalid value {} for config setting '{}', ignoring", "Warning".yellow(), v, key);
+            }
+            _ => {
+                eprintln!("{}: Unknown config setting '{}', ignoring", "Warning".yellow(), key);
             }
         }
     }
     
-    Ok(tabs)
+    Ok(config)
 }
 
-fn parse_session_tabs(session: &SessionInfo) -> Result<Vec<TabInfo>> {
-    if session.is_exited {
-        // Try to load from cache for exited sessions
-        match load_cached_session_layout(&session.name) {
-            Ok(layout) => parse_kdl_layout(&layout),
-            Err(_) => {
-                // If we can't load cached layout, return empty
-                Ok(Vec::new())
+/// Each role names a color, e.g. `theme { name "magenta"; cwd "bright black" }`
+fn parse_theme(doc: &kdl::KdlDocument, theme: &mut Theme) {
+    for node in doc.nodes() {
+        let role = node.name().value();
+        let slot = match role {
+            "prefix" => &mut theme.prefix,
+            "name" => &mut theme.name,
+            "current" => &mut theme.current,
+            "command" => &mut theme.command,
+            "cwd" => &mut theme.cwd,
+            "tab" => &mut theme.tab,
+            _ => {
+                eprintln!("{}: Unknown theme color '{}', ignoring", "Warning".yellow(), role);
+                continue;
             }
-        }
-    } else {
-        // Get the layout dump for live sessions
-        let layout = cmd!("zellij", "-s", &session.name, "action", "dump-layout")
-            .stderr_null()
-            .read()
-            .context("Failed to dump layout")?;
+        };
         
-        parse_kdl_layout(&layout)
+        let value = node.entries().iter()
+            .find(|e| e.name().is_none())
+            .and_then(|e| e.value().as_string());
+        match value.map(|name| name.parse::<Color>()) {
+            Some(Ok(color)) => *slot = Some(color),
+            _ => {
+                eprintln!("{}: Invalid color for theme '{}', using the default", "Warning".yellow(), role);
+            }
+        }
     }
 }
 
-fn check_dead_session(name: &str) -> Result<Option<SessionInfo>> {
-    // List all sessions including exited ones
-    let all_sessions = list_sessions(true)?;
+fn load_config() -> Config {
+    let path = match get_config_path() {
+        Some(path) if path.exists() => path,
+        _ => return Config::default(),
+    };
     
-    // Find a dead session with the given name
-    Ok(all_sessions.into_iter()
-        .find(|s| s.name == name && s.is_exited))
+    let result = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {:?}", path))
+        .and_then(|contents| parse_config(&contents));
+    
+    match result {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}: {:#}, using defaults", "Warning".yellow(), e);
+            Config::default()
+        }
+    }
 }
 
-fn resurrect_dead_session(name: &str) -> Result<()> {
-    println!("{}: Resurrecting dead session '{}'", "Info".blue(), name.green());
-    
-    // Try to get the original working directory from the cached layout
-    let original_cwd = match load_cached_session_layout(name) {
-        Ok(layout) => get_layout_cwd(&layout),
-        Err(_) => None,
-    };
+fn sort_sessions(sessions: &mut [SessionInfo], sort: Option<SortKey>, reverse: bool) {
+    match sort {
+        Some(SortKey::Name) => sessions.sort_by(|a, b| a.name.cmp(&b.name)),
+        // Oldest first, sessions with an unknown age go last
+        Some(SortKey::Created) => sessions.sort_by_key(|s| std::cmp::Reverse(s.created)),
+        // Needs tab information, handled by sort_sessions_by_tabs
+        Some(SortKey::Tabs) | None => {}
+    }
+    if reverse && sort != Some(SortKey::Tabs) {
+        sessions.reverse();
+    }
+    // Pinned sessions go first in every view, otherwise keeping the requested order
+    sessions.sort_by_key(|s| !s.meta.pinned);
+}
+
+fn sort_sessions_by_tabs(sessions_with_tabs: &mut [(SessionInfo, Result<Vec<TabInfo>>)], sort: Option<SortKey>, reverse: bool) {
+    if sort != Some(SortKey::Tabs) {
+        return;
+    }
+    sessions_with_tabs.sort_by_key(|(_, tabs)| {
+        std::cmp::Reverse(tabs.as_ref().map(|tabs| tabs.len()).unwrap_or(0))
+    });
+    if reverse {
+        sessions_with_tabs.reverse();
+    }
+    sessions_with_tabs.sort_by_key(|(session, _)| !session.meta.pinned);
+}
+
+/// Everything `z` needs from a terminal multiplexer
+trait Backend: Sync {
+    /// Executable that has to be on PATH for this backend to work
+    fn binary(&self) -> &'static str;
+    
+    /// Shown when the binary is missing
+    fn install_hint(&self) -> &'static str;
+    
+    /// Name of the session this process is running inside, if any
+    fn current_session(&self) -> Option<String>;
+    
+    /// All sessions, exited ones included, in the multiplexer's own order
+    fn list_sessions(&self) -> Result<Vec<ListedSession>>;
+    
+    fn session_tabs(&self, session: &SessionInfo, config: &Config) -> Result<Vec<TabInfo>>;
+    
+    /// The session's layout as KDL, suitable for passing back to `create`
+    fn layout(&self, session: &SessionInfo, config: &Config) -> Result<String>;
+    
+    fn create(&self, name: &str, cwd: Option<&str>, layout: Option<&Path>, detached: bool, config: &Config) -> Result<()>;
+    
+    /// Create a session with a single pane running `command` through the shell
+    fn create_running(&self, name: &str, command: &str, detached: bool, config: &Config) -> Result<()>;
+    
+    fn attach(&self, name: &str, cwd: Option<&str>, config: &Config) -> Result<()>;
+    
+    /// Bring an exited session back in the background, without attaching to it
+    fn resurrect(&self, name: &str, cwd: Option<&str>) -> Result<()>;
+    
+    fn switch(&self, name: &str) -> Result<()>;
+    
+    /// Make the tab with this name the active one in a session
+    fn focus_tab(&self, name: &str, tab: &str) -> Result<()>;
+    
+    /// Same as `focus_tab`, by 1-based position instead of name
+    fn focus_tab_index(&self, name: &str, index: usize) -> Result<()>;
+    
+    /// Open a new tab in a session, starting `command` in it through the shell
+    fn new_tab(&self, name: &str, tab: &str, cwd: Option<&str>, command: Option<&str>) -> Result<()>;
+    
+    /// Open a new tab in a session laid out like `layout`, a single tab's panes
+    fn new_tab_from_layout(&self, name: &str, tab: &str, layout: &Path) -> Result<()>;
+    
+    /// Write text to the focused pane of a session as if it were typed
+    fn write_chars(&self, name: &str, text: &str) -> Result<()>;
+    
+    /// Detach the client running this process from its session
+    fn detach(&self) -> Result<()>;
+    
+    fn kill(&self, name: &str) -> Result<()>;
+    
+    /// Remove a session for good, including any resurrectable state
+    fn delete(&self, name: &str) -> Result<()>;
+    
+    fn rename(&self, old_name: &str, new_name: &str) -> Result<()>;
+}
+
+struct Zellij;
+
+impl Backend for Zellij {
+    fn binary(&self) -> &'static str {
+        "zellij"
+    }
+    
+    fn install_hint(&self) -> &'static str {
+        "Install it from https://zellij.dev/documentation/installation or use --backend tmux"
+    }
+    
+    fn current_session(&self) -> Option<String> {
+        env::var("ZELLIJ_SESSION_NAME").ok()
+    }
+    
+    fn list_sessions(&self) -> Result<Vec<ListedSession>> {
+        // Plain output doesn't shift with zellij's coloring, but older versions lack the flag
+        let output = match cmd!("zellij", "list-sessions", "--no-formatting").stderr_null().read() {
+            Ok(output) => output,
+            Err(_) => cmd!("zellij", "list-sessions")
+                .read()
+                .context("Failed to list zellij sessions")?,
+        };
+        
+        Ok(parse_zellij_sessions(&output))
+    }
+    
+    fn session_tabs(&self, session: &SessionInfo, config: &Config) -> Result<Vec<TabInfo>> {
+        parse_session_tabs(session, config)
+    }
+    
+    fn layout(&self, session: &SessionInfo, config: &Config) -> Result<String> {
+        if session.is_exited {
+            load_cached_session_layout(&session.name)
+        } else {
+            dump_session_layout(&session.name, config.layout_timeout)
+        }
+    }
+    
+    fn create(&self, name: &str, cwd: Option<&str>, layout: Option<&Path>, detached: bool, config: &Config) -> Result<()> {
+        let mut expression = cmd("zellij", session_create_args(name, layout, config));
+        if let Some(cwd) = cwd {
+            expression = expression.dir(cwd);
+        }
+        
+        if detached {
+            // zellij can't create a session without attaching, so run it in the background
+            expression
+                .stderr_null()
+                .stdout_null()
+                .start()?;
+        } else {
+            // stdin may have been used up by a piped layout, but the client needs the terminal
+            if !io::stdin().is_terminal() {
+                expression = expression.stdin_path("/dev/tty");
+            }
+            expression
+                .run()
+                .context("Failed to create session")?;
+        }
+        Ok(())
+    }
+    
+    fn create_running(&self, name: &str, command: &str, detached: bool, config: &Config) -> Result<()> {
+        let shell = config.shell.as_deref().unwrap_or("sh");
+        let layout = format!("layout {{\n    pane command={} {{\n        args \"-c\" {}\n    }}\n}}\n",
+            kdl::KdlValue::String(shell.to_string()),
+            kdl::KdlValue::String(command.to_string()));
+        
+        // Kept on disk rather than in a temp file, since a detached zellij reads it asynchronously
+        let layout_path = get_z_cache_dir()
+            .context("Could not determine cache directory")?
+            .join("runs")
+            .join(format!("{}.kdl", name));
+        fs::create_dir_all(layout_path.parent().unwrap())?;
+        fs::write(&layout_path, layout)
+            .with_context(|| format!("Failed to write layout to {:?}", layout_path))?;
+        
+        self.create(name, None, Some(&layout_path), detached, config)
+    }
+    
+    fn attach(&self, name: &str, cwd: Option<&str>, config: &Config) -> Result<()> {
+        let mut args = vec!["attach".to_string(), name.to_string()];
+        args.extend(config.passthrough.iter().cloned());
+        let mut expression = cmd("zellij", args);
+        if let Some(cwd) = cwd {
+            expression = expression.dir(cwd);
+        }
+        expression
+            .run()
+            .context("Failed to attach to session")?;
+        Ok(())
+    }
+    
+    fn resurrect(&self, name: &str, cwd: Option<&str>) -> Result<()> {
+        let mut expression = cmd!("zellij", "attach", "--create-background", name)
+            .stdin_null()
+            .stdout_null();
+        if let Some(cwd) = cwd {
+            expression = expression.dir(cwd);
+        }
+        expression
+            .run()
+            .context("Failed to resurrect session")?;
+        Ok(())
+    }
+    
+    fn switch(&self, name: &str) -> Result<()> {
+        cmd!("zellij", "action", "switch-session", name).run()?;
+        Ok(())
+    }
+    
+    fn focus_tab(&self, name: &str, tab: &str) -> Result<()> {
+        cmd!("zellij", "--session", name, "action", "go-to-tab-name", tab).run()?;
+        Ok(())
+    }
+    
+    fn focus_tab_index(&self, name: &str, index: usize) -> Result<()> {
+        cmd!("zellij", "--session", name, "action", "go-to-tab", index.to_string()).run()?;
+        Ok(())
+    }
+    
+    fn new_tab(&self, name: &str, tab: &str, cwd: Option<&str>, command: Option<&str>) -> Result<()> {
+        let mut args = vec!["--session", name, "action", "new-tab", "--name", tab];
+        if let Some(cwd) = cwd {
+            args.extend(["--cwd", cwd]);
+        }
+        cmd("zellij", args).run()?;
+        
+        // The new tab is focused, so typing the command starts it in the tab's shell
+        if let Some(command) = command {
+            self.write_chars(name, &format!("{}\n", command))?;
+        }
+        Ok(())
+    }
+    
+    fn new_tab_from_layout(&self, name: &str, tab: &str, layout: &Path) -> Result<()> {
+        cmd!("zellij", "--session", name, "action", "new-tab", "--layout", layout, "--name", tab).run()?;
+        Ok(())
+    }
+    
+    fn write_chars(&self, name: &str, text: &str) -> Result<()> {
+        cmd!("zellij", "--session", name, "action", "write-chars", text).run()?;
+        Ok(())
+    }
+    
+    fn detach(&self) -> Result<()> {
+        cmd!("zellij", "action", "detach").run()?;
+        Ok(())
+    }
+    
+    fn kill(&self, name: &str) -> Result<()> {
+        cmd!("zellij", "kill-session", name).run()?;
+        Ok(())
+    }
+    
+    fn delete(&self, name: &str) -> Result<()> {
+        cmd!("zellij", "delete-session", name).run()?;
+        Ok(())
+    }
+    
+    fn rename(&self, old_name: &str, new_name: &str) -> Result<()> {
+        // Check if we're renaming the current session
+        let in_current = self.current_session()
+            .map(|current| current == old_name)
+            .unwrap_or(false);
+        
+        if in_current {
+            // Use action command when inside the session
+            cmd!("zellij", "action", "rename-session", new_name).run()?;
+        } else {
+            // Use regular command when outside
+            cmd!("zellij", "rename-session", old_name, new_name).run()?;
+        }
+        Ok(())
+    }
+}
+
+struct Tmux;
+
+impl Tmux {
+    /// Target a session by exact name rather than tmux's prefix matching
+    fn target(name: &str) -> String {
+        format!("={}", name)
+    }
+}
+
+impl Backend for Tmux {
+    fn binary(&self) -> &'static str {
+        "tmux"
+    }
+    
+    fn install_hint(&self) -> &'static str {
+        "Install it with your package manager or use --backend zellij"
+    }
+    
+    fn current_session(&self) -> Option<String> {
+        env::var_os("TMUX")?;
+        cmd!("tmux", "display-message", "-p", "#S")
+            .stderr_null()
+            .read()
+            .ok()
+            .map(|name| name.trim().to_string())
+    }
+    
+    fn list_sessions(&self) -> Result<Vec<ListedSession>> {
+        let output = cmd!("tmux", "list-sessions", "-F", "#{session_created}\t#{session_name}")
+            .stdout_capture()
+            .stderr_null()
+            .unchecked()
+            .run()
+            .context("Failed to list tmux sessions")?;
+        
+        // tmux exits non-zero when no server is running, which just means no sessions
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+        
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(created, name)| ListedSession {
+                name: name.to_string(),
+                is_exited: false,
+                is_current: false,
+                // tmux reports a unix timestamp, turn it into an age like zellij's
+                created: created.parse::<u64>().ok()
+                    .map(|secs| now.saturating_sub(Duration::from_secs(secs))),
+            })
+            .collect())
+    }
+    
+    fn session_tabs(&self, session: &SessionInfo, _config: &Config) -> Result<Vec<TabInfo>> {
+        let output = cmd!("tmux", "list-panes", "-s", "-t", Tmux::target(&session.name), "-F",
+            "#{window_index}\t#{window_active}\t#{window_name}\t#{pane_active}\t#{pane_current_command}\t#{pane_current_path}")
+            .stderr_null()
+            .read()
+            .context("Failed to list tmux panes")?;
+        
+        let panes: Vec<Vec<&str>> = output.lines()
+            .map(|line| line.splitn(6, '\t').collect::<Vec<&str>>())
+            .filter(|fields| fields.len() == 6)
+            .collect();
+        
+        // Windows map onto tabs, collapsing identical panes like zellij
+        let mut tabs: Vec<TabInfo> = Vec::new();
+        let mut last_index = None;
+        for fields in &panes {
+            if let [index, active, name, pane_active, command, cwd] = fields[..] {
+                if last_index != Some(index) {
+                    last_index = Some(index);
+                    tabs.push(TabInfo {
+                        name: name.to_string(),
+                        panes: Vec::new(),
+                        pane_count: 0,
+                        focused: active == "1",
+                        command: None,
+                        cwd: None,
+                        branch: None,
+                    });
+                }
+                
+                let tab = tabs.last_mut().unwrap();
+                let pane = PaneInfo {
+                    command: Some(command.to_string()).filter(|c| !c.is_empty()),
+                    cwd: Some(cwd.to_string()).filter(|c| !c.is_empty()),
+                    floating: false,
+                };
+                if pane_active == "1" {
+                    tab.command.clone_from(&pane.command);
+                    tab.cwd.clone_from(&pane.cwd);
+                }
+                tab.pane_count += 1;
+                if !tab.panes.contains(&pane) {
+                    tab.panes.push(pane);
+                }
+            }
+        }
+        
+        Ok(tabs)
+    }
+    
+    fn layout(&self, _session: &SessionInfo, _config: &Config) -> Result<String> {
+        bail!("Layouts are not supported by the tmux backend")
+    }
+    
+    fn create(&self, name: &str, cwd: Option<&str>, layout: Option<&Path>, detached: bool, config: &Config) -> Result<()> {
+        if layout.is_some() {
+            bail!("Layouts are not supported by the tmux backend");
+        }
+        
+        let mut args = vec!["new-session".to_string(), "-s".to_string(), name.to_string()];
+        if detached {
+            args.push("-d".to_string());
+        }
+        if let Some(cwd) = cwd {
+            args.extend(["-c".to_string(), cwd.to_string()]);
+        }
+        // Flags have to come before the shell command
+        args.extend(config.passthrough.iter().cloned());
+        if let Some(shell) = &config.shell {
+            args.push(shell.clone());
+        }
+        
+        cmd("tmux", args)
+            .run()
+            .context("Failed to create session")?;
+        Ok(())
+    }
+    
+    fn create_running(&self, name: &str, command: &str, detached: bool, config: &Config) -> Result<()> {
+        let mut args = vec!["new-session", "-s", name];
+        if detached {
+            args.push("-d");
+        }
+        args.extend(config.passthrough.iter().map(String::as_str));
+        // tmux already runs a trailing command through the shell
+        args.push(command);
+        
+        cmd("tmux", args)
+            .run()
+            .context("Failed to create session")?;
+        Ok(())
+    }
+    
+    fn attach(&self, name: &str, _cwd: Option<&str>, config: &Config) -> Result<()> {
+        let mut args = vec!["attach-session".to_string(), "-t".to_string(), Tmux::target(name)];
+        args.extend(config.passthrough.iter().cloned());
+        cmd("tmux", args)
+            .run()
+            .context("Failed to attach to session")?;
+        Ok(())
+    }
+    
+    fn switch(&self, name: &str) -> Result<()> {
+        cmd!("tmux", "switch-client", "-t", Tmux::target(name)).run()?;
+        Ok(())
+    }
+    
+    fn focus_tab(&self, name: &str, tab: &str) -> Result<()> {
+        cmd!("tmux", "select-window", "-t", format!("{}:{}", Tmux::target(name), tab)).run()?;
+        Ok(())
+    }
+    
+    fn focus_tab_index(&self, name: &str, index: usize) -> Result<()> {
+        // Window numbering depends on base-index, so look up the nth window's real index
+        let windows = cmd!("tmux", "list-windows", "-t", Tmux::target(name), "-F", "#{window_index}")
+            .stderr_null()
+            .read()
+            .context("Failed to list tmux windows")?;
+        let window = windows.lines().nth(index.saturating_sub(1))
+            .with_context(|| format!("Session '{}' has no window {}", name, index))?;
+        
+        cmd!("tmux", "select-window", "-t", format!("{}:{}", Tmux::target(name), window)).run()?;
+        Ok(())
+    }
+    
+    fn new_tab(&self, name: &str, tab: &str, cwd: Option<&str>, command: Option<&str>) -> Result<()> {
+        let target = format!("{}:", Tmux::target(name));
+        let mut args = vec!["new-window", "-t", target.as_str(), "-n", tab];
+        if let Some(cwd) = cwd {
+            args.extend(["-c", cwd]);
+        }
+        if let Some(command) = command {
+            args.push(command);
+        }
+        cmd("tmux", args).run()?;
+        Ok(())
+    }
+    
+    fn new_tab_from_layout(&self, _name: &str, _tab: &str, _layout: &Path) -> Result<()> {
+        bail!("Layouts are not supported by the tmux backend")
+    }
+    
+    fn write_chars(&self, name: &str, text: &str) -> Result<()> {
+        // -l sends the text literally instead of looking up key names
+        cmd!("tmux", "send-keys", "-t", Tmux::target(name), "-l", text).run()?;
+        Ok(())
+    }
+    
+    fn detach(&self) -> Result<()> {
+        cmd!("tmux", "detach-client").run()?;
+        Ok(())
+    }
+    
+    fn kill(&self, name: &str) -> Result<()> {
+        cmd!("tmux", "kill-session", "-t", Tmux::target(name)).run()?;
+        Ok(())
+    }
+    
+    fn delete(&self, name: &str) -> Result<()> {
+        // tmux has no exited sessions, so killing is already permanent
+        self.kill(name)
+    }
+    
+    fn resurrect(&self, name: &str, _cwd: Option<&str>) -> Result<()> {
+        bail!("tmux sessions can't be resurrected once '{}' has ended", name)
+    }
+    
+    fn rename(&self, old_name: &str, new_name: &str) -> Result<()> {
+        cmd!("tmux", "rename-session", "-t", Tmux::target(old_name), new_name).run()?;
+        Ok(())
+    }
+}
+
+fn find_in_path(binary: &str) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    
+    let path_var = env::var_os("PATH")?;
+    env::split_paths(&path_var)
+        .map(|dir| dir.join(binary))
+        .find(|candidate| {
+            fs::metadata(candidate)
+                .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+}
+
+fn ensure_backend_installed(backend: &dyn Backend) -> Result<()> {
+    if find_in_path(backend.binary()).is_none() {
+        bail!("{} is not installed or not on your PATH. {}.", backend.binary(), backend.install_hint());
+    }
+    Ok(())
+}
+
+fn apply_color_choice(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            // https://no-color.org: any non-empty value disables color
+            let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            !no_color && io::stdout().is_terminal()
+        }
+    };
+    colored::control::set_override(enabled);
+}
+
+fn get_zellij_version() -> Result<String> {
+    // Asked for once per exited session when loading cached layouts, so only work it out once
+    static VERSION: OnceLock<String> = OnceLock::new();
+    if let Some(version) = VERSION.get() {
+        return Ok(version.clone());
+    }
+    
+    let key = zellij_binary_key();
+    let cache_path = get_z_cache_dir().map(|dir| dir.join("zellij-version"));
+    let cached = key.as_ref().zip(cache_path.as_ref())
+        .and_then(|(key, path)| {
+            let contents = fs::read_to_string(path).ok()?;
+            let (cached_key, version) = contents.trim_end().split_once('\n')?;
+            (cached_key == key).then(|| version.to_string())
+        });
+    
+    let version = match cached {
+        Some(version) => version,
+        None => {
+            let version = query_zellij_version()?;
+            if let (Some(key), Some(path)) = (&key, &cache_path) {
+                // Only saves a process on the next run, so failing to write it doesn't matter
+                if let Some(parent) = path.parent() {
+                    let _ = fs::create_dir_all(parent);
+                }
+                let _ = fs::write(path, format!("{}\n{}\n", key, version));
+            }
+            version
+        }
+    };
+    Ok(VERSION.get_or_init(|| version).clone())
+}
+
+/// The zellij binary's path and mtime, so an upgrade invalidates the persisted version
+fn zellij_binary_key() -> Option<String> {
+    let binary = find_in_path("zellij")?;
+    let binary = binary.canonicalize().unwrap_or(binary);
+    let modified = fs::metadata(&binary).ok()?.modified().ok()?;
+    let mtime = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{} {}", binary.display(), mtime.as_secs()))
+}
+
+fn query_zellij_version() -> Result<String> {
+    let output = cmd!("zellij", "--version")
+        .read()
+        .context("Failed to get zellij version")?;
+    
+    // Parse "zellij 0.42.2" to get "0.42.2"
+    let version = output
+        .split_whitespace()
+        .nth(1)
+        .context("Failed to parse zellij version")?
+        .to_string();
+    
+    Ok(version)
+}
+
+fn get_zellij_cache_dir() -> Result<PathBuf> {
+    let version = get_zellij_version()?;
+    
+    // Like zellij itself, an explicit XDG_CACHE_HOME wins on every platform
+    let xdg_cache_home = env::var("XDG_CACHE_HOME").ok().filter(|dir| !dir.is_empty());
+    let cache_base = if let Some(cache_home) = xdg_cache_home {
+        PathBuf::from(cache_home)
+            .join("zellij")
+            .join(&version)
+    } else if cfg!(target_os = "macos") {
+        let home = env::var("HOME").context("HOME not set")?;
+        PathBuf::from(home)
+            .join("Library")
+            .join("Caches")
+            .join("org.Zellij-Contributors.Zellij")
+            .join(&version)
+    } else {
+        // Linux and others
+        let home = env::var("HOME").context("HOME not set")?;
+        PathBuf::from(home)
+            .join(".cache")
+            .join("zellij")
+            .join(&version)
+    };
+    
+    Ok(cache_base)
+}
+
+fn load_cached_session_layout(session_name: &str) -> Result<String> {
+    let cache_dir = get_zellij_cache_dir()?;
+    let layout_path = cache_dir
+        .join("session_info")
+        .join(session_name)
+        .join("session-layout.kdl");
+    
+    if layout_path.exists() {
+        fs::read_to_string(&layout_path)
+            .with_context(|| format!("Failed to read cached layout from {:?}", layout_path))
+    } else {
+        bail!("No cached layout found for session {}", session_name)
+    }
+}
+
+/// Length of a blake3 hash in hex, the longest prefix we can hand out
+const HASH_HEX_LEN: usize = 64;
+
+fn compute_hash_prefix(name: &str, len: usize) -> String {
+    let hash = blake3::hash(name.as_bytes());
+    hash.to_hex().chars().take(len.clamp(1, HASH_HEX_LEN)).collect()
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count()
+}
+
+fn find_shortest_prefixes<T: AsRef<SessionInfo>>(sessions: &[T]) -> HashMap<String, String> {
+    // Once sorted, the hashes sharing the most with any given one are its neighbours
+    let mut sorted: Vec<&SessionInfo> = sessions.iter().map(|s| s.as_ref()).collect();
+    sorted.sort_by(|a, b| a.hash_prefix.cmp(&b.hash_prefix));
+    
+    let mut prefixes = HashMap::with_capacity(sorted.len());
+    for (i, session) in sorted.iter().enumerate() {
+        let hash = &session.hash_prefix;
+        let shared_before = i.checked_sub(1)
+            .map_or(0, |prev| common_prefix_len(hash, &sorted[prev].hash_prefix));
+        let shared_after = sorted.get(i + 1)
+            .map_or(0, |next| common_prefix_len(hash, &next.hash_prefix));
+        
+        // One more character than it shares with either neighbour, unless the whole prefix collides
+        let len = (shared_before.max(shared_after) + 1).min(hash.len());
+        prefixes.insert(session.name.clone(), hash[..len].to_string());
+    }
+    
+    prefixes
+}
+
+fn find_session<'a>(query: &str, sessions: &'a [SessionInfo]) -> Result<Option<&'a SessionInfo>> {
+    // Exact name or hash prefix always wins
+    if let Some(session) = sessions.iter().find(|s| s.name == query || s.hash_prefix.starts_with(query)) {
+        return Ok(Some(session));
+    }
+    
+    // Fall back to a case-insensitive substring match on the name
+    let needle = query.to_lowercase();
+    let candidates: Vec<&SessionInfo> = sessions.iter()
+        .filter(|s| s.name.to_lowercase().contains(&needle))
+        .collect();
+    
+    match candidates.len() {
+        0 => Ok(None),
+        1 => Ok(Some(candidates[0])),
+        _ => {
+            let prefixes = find_shortest_prefixes(sessions);
+            // Kept off stdout, which may be --json output or piped somewhere
+            eprintln!("{}: '{}' matches multiple sessions:", "Warning".yellow(), query);
+            for candidate in &candidates {
+                eprintln!("  {} {}", 
+                    prefixes.get(&candidate.name).unwrap().yellow().bold(),
+                    candidate.name.cyan()
+                );
+            }
+            bail!("Ambiguous session '{}'. Use a longer name or a hash prefix.", query)
+        }
+    }
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip a CSI sequence: ESC [ parameters... final byte in @..~
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Parse zellij's `[Created 2h 3m ago]` annotation into an age
+fn parse_created_age(line: &str) -> Option<Duration> {
+    let start = line.find("[Created ")? + "[Created ".len();
+    let end = start + line[start..].find(" ago]")?;
+    
+    let mut total = 0u64;
+    for part in line[start..end].split_whitespace() {
+        let digits_end = part.find(|c: char| !c.is_ascii_digit())?;
+        let value: u64 = part[..digits_end].parse().ok()?;
+        let unit_secs = match &part[digits_end..] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            "M" | "month" | "months" => 30 * 24 * 60 * 60,
+            "y" | "year" | "years" => 365 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total += value * unit_secs;
+    }
+    
+    Some(Duration::from_secs(total))
+}
+
+fn parse_zellij_sessions(output: &str) -> Vec<ListedSession> {
+    output
+        .lines()
+        .map(strip_ansi)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            // Lines look like: `name [Created 2h 3m ago] (EXITED - attach to resurrect)`
+            let name = match line.find(" [Created ") {
+                Some(end) => line[..end].trim().to_string(),
+                None => line.split_whitespace().next().unwrap_or("").to_string(),
+            };
+            
+            ListedSession {
+                name,
+                is_exited: line.contains("(EXITED"),
+                is_current: line.contains("(current)"),
+                created: parse_created_age(&line),
+            }
+        })
+        .collect()
+}
+
+fn list_sessions(include_exited: bool, config: &Config) -> Result<Vec<SessionInfo>> {
+    let backend = config.backend();
+    let current_session = backend.current_session();
+    
+    let sessions: Vec<SessionInfo> = backend.list_sessions()?
+        .into_iter()
+        .filter(|s| !s.name.is_empty() && (include_exited || !s.is_exited))
+        .map(|ListedSession { name, is_exited, is_current, created }| {
+            let is_current = is_current || current_session.as_ref() == Some(&name);
+            let hash_prefix = compute_hash_prefix(&name, config.hash_len);
+            let meta = load_session_meta(&name);
+            SessionInfo { name, is_current, is_exited, hash_prefix, created, meta }
+        })
+        .collect();
+    
+    Ok(sessions)
+}
+
+fn get_layout_cwd(layout: &str) -> Option<String> {
+    // Parse KDL and extract the cwd from layout node
+    let doc = layout.parse::<kdl::KdlDocument>().ok()?;
+    let layout_node = doc.nodes().iter().find(|n| n.name().value() == "layout")?;
+    layout_root_cwd(layout_node)
+}
+
+/// `dump-layout` writes the session's cwd as a `cwd "..."` child of `layout`, hand-written layouts
+/// may use a `cwd=` entry instead
+fn layout_root_cwd(layout_node: &kdl::KdlNode) -> Option<String> {
+    let child = layout_node.children()
+        .and_then(|doc| doc.nodes().iter().find(|n| n.name().value() == "cwd"))
+        .and_then(|node| node.entries().first())
+        .and_then(|entry| entry.value().as_string())
+        .map(|cwd| cwd.to_string());
+    child.or_else(|| node_string_entry(layout_node, "cwd"))
+}
+
+/// Pane and tab cwds are relative to the enclosing one, all the way up to the layout's
+fn resolve_cwd(cwd: Option<String>, base: Option<&str>) -> Option<String> {
+    match (cwd, base) {
+        (Some(cwd), Some(base)) if Path::new(&cwd).is_relative() => {
+            Some(Path::new(base).join(cwd).to_string_lossy().to_string())
+        }
+        (Some(cwd), _) => Some(cwd),
+        (None, base) => base.map(|base| base.to_string()),
+    }
+}
+
+/// Each tab of a layout as a layout of its own, for replaying it with `new-tab --layout`
+fn split_layout_tabs(layout: &str) -> Result<Vec<String>> {
+    let doc = layout.parse::<kdl::KdlDocument>()
+        .context("Failed to parse KDL layout")?;
+    let layout_node = doc.nodes().iter().find(|n| n.name().value() == "layout")
+        .context("Layout has no layout node")?;
+    let layout_cwd = layout_root_cwd(layout_node);
+    
+    let mut tabs = Vec::new();
+    let nodes = layout_node.children().map(|children| children.nodes()).unwrap_or_default();
+    for tab in nodes.iter().filter(|n| n.name().value() == "tab") {
+        let mut body = kdl::KdlDocument::new();
+        // Pane cwds are relative to the tab's, which has no tab node left to live on
+        if let Some(cwd) = resolve_cwd(node_string_entry(tab, "cwd"), layout_cwd.as_deref()) {
+            let mut cwd_node = kdl::KdlNode::new("cwd");
+            cwd_node.push(cwd);
+            body.nodes_mut().push(cwd_node);
+        }
+        if let Some(children) = tab.children() {
+            body.nodes_mut().extend(children.nodes().iter().cloned());
+        }
+        
+        let mut single = kdl::KdlNode::new("layout");
+        single.set_children(body);
+        let mut doc = kdl::KdlDocument::new();
+        doc.nodes_mut().push(single);
+        doc.fmt();
+        tabs.push(doc.to_string());
+    }
+    Ok(tabs)
+}
+
+fn node_string_entry(node: &kdl::KdlNode, key: &str) -> Option<String> {
+    node.entries().iter()
+        .find(|e| e.name().map(|n| n.value()) == Some(key))
+        .and_then(|e| e.value().as_string())
+        .map(|v| v.to_string())
+}
+
+fn node_bool_entry(node: &kdl::KdlNode, key: &str) -> Option<bool> {
+    node.entries().iter()
+        .find(|e| e.name().map(|n| n.value()) == Some(key))
+        .and_then(|e| e.value().as_bool())
+}
+
+/// Plugin panes such as the tab and status bars, declared either as a
+/// `plugin` child node or a `plugin=` entry
+fn is_plugin_pane(node: &kdl::KdlNode) -> bool {
+    node_string_entry(node, "plugin").is_some()
+        || node.children()
+            .map(|doc| doc.nodes().iter().any(|n| n.name().value() == "plugin"))
+            .unwrap_or(false)
+}
+
+fn collect_panes(children: &kdl::KdlDocument, floating: bool, base_cwd: Option<&str>, panes: &mut Vec<PaneInfo>, focused: &mut Option<PaneInfo>) {
+    for child in children.nodes() {
+        // Floating panes sit in their own block alongside the tiled ones
+        if child.name().value() == "floating_panes" {
+            if let Some(floating_children) = child.children() {
+                collect_panes(floating_children, true, base_cwd, panes, focused);
+            }
+            continue;
+        }
+        if child.name().value() != "pane" || is_plugin_pane(child) {
+            continue;
+        }
+        
+        // Split containers and stacks only group other panes, so descend into them
+        let is_stack = node_bool_entry(child, "stacked") == Some(true);
+        let nested = child.children()
+            .filter(|doc| is_stack || doc.nodes().iter().any(|n| n.name().value() == "pane"));
+        let cwd = resolve_cwd(node_string_entry(child, "cwd"), base_cwd);
+        if let Some(nested) = nested {
+            collect_panes(nested, floating, cwd.as_deref(), panes, focused);
+            continue;
+        }
+        
+        let command = node_string_entry(child, "command");
+        let pane = PaneInfo { command, cwd, floating };
+        if node_bool_entry(child, "focus") == Some(true) {
+            *focused = Some(pane.clone());
+        }
+        panes.push(pane);
+    }
+}
+
+fn parse_kdl_layout(layout: &str) -> Result<Vec<TabInfo>> {
+    // Parse KDL
+    let doc = layout.parse::<kdl::KdlDocument>()
+        .context("Failed to parse KDL layout")?;
+    
+    let mut tabs = Vec::new();
+    
+    // Find the layout node first
+    if let Some(layout_node) = doc.nodes().iter().find(|n| n.name().value() == "layout") {
+        let layout_cwd = layout_root_cwd(layout_node);
+        if let Some(layout_children) = layout_node.children() {
+            // Now find all tab nodes within the layout
+            for node in layout_children.nodes() {
+                if node.name().value() == "tab" {
+                    let mut tab_name = String::from("Tab");
+                    let mut panes_info: Vec<PaneInfo> = Vec::new();
+                    
+                    // Get tab name if present
+                    if let Some(name_entry) = node.entries().iter().find(|e| e.name().map(|n| n.value()) == Some("name"))
+                        && let Some(name_val) = name_entry.value().as_string()
+                    {
+                        tab_name = name_val.to_string();
+                    }
+                    
+                    // Look through child nodes for panes, at any depth
+                    let tab_cwd = resolve_cwd(node_string_entry(node, "cwd"), layout_cwd.as_deref());
+                    let mut focused_pane = None;
+                    if let Some(children) = node.children() {
+                        collect_panes(children, false, tab_cwd.as_deref(), &mut panes_info, &mut focused_pane);
+                    }
+                    let command = focused_pane.as_ref().or(panes_info.first())
+                        .and_then(|pane| pane.command.clone());
+                    let cwd = focused_pane.and_then(|pane| pane.cwd)
+                        .or(tab_cwd)
+                        .or_else(|| panes_info.first().and_then(|pane| pane.cwd.clone()));
+                    
+                    let pane_count = panes_info.len();
+                    
+                    // Collapse identical panes, keeping the first of each combination
+                    let mut seen = std::collections::HashSet::new();
+                    let panes = panes_info.into_iter()
+                        .filter(|pane| seen.insert(pane.clone()))
+                        .collect();
+                    
+                    tabs.push(TabInfo {
+                        name: tab_name,
+                        panes,
+                        pane_count,
+                        focused: node_bool_entry(node, "focus") == Some(true),
+                        command,
+                        cwd,
+                        branch: None,
+                    });
+                }
+            }
+        }
+    }
+    
+    Ok(tabs)
+}
+
+fn parse_session_tabs(session: &SessionInfo, config: &Config) -> Result<Vec<TabInfo>> {
+    if session.is_exited {
+        // Try to load from cache for exited sessions
+        match load_cached_session_layout(&session.name) {
+            Ok(layout) => parse_kdl_layout(&layout),
+            Err(_) => {
+                // If we can't load cached layout, return empty
+                Ok(Vec::new())
+            }
+        }
+    } else {
+        // Get the layout dump for live sessions
+        let layout = dump_session_layout(&session.name, config.layout_timeout)?;
+        parse_kdl_layout(&layout)
+    }
+}
+
+fn dump_session_layout(name: &str, timeout: Duration) -> Result<String> {
+    let handle = cmd!("zellij", "-s", name, "action", "dump-layout")
+        .stdout_capture()
+        .stderr_null()
+        .start()
+        .context("Failed to dump layout")?;
+    
+    // A wedged session never answers, and would otherwise stall the whole listing
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(output) = handle.try_wait().context("Failed to dump layout")? {
+            return String::from_utf8(output.stdout.clone())
+                .context("Layout dump is not valid UTF-8");
+        }
+        if Instant::now() >= deadline {
+            let _ = handle.kill();
+            bail!("Timed out dumping layout of session '{}'", name);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn confirm(question: &str, default_yes: bool, config: &Config) -> Result<bool> {
+    let hint = if default_yes { "[Y/n]" } else { "[y/N]" };
+    print!("{} {} ", question, hint);
+    
+    // Still show the question so logs make sense, just answer it ourselves
+    if config.assume_yes {
+        println!("y");
+        return Ok(true);
+    }
+    if config.assume_no {
+        println!("n");
+        return Ok(false);
+    }
+    // Piped or cron stdin reads as empty, which would otherwise quietly take the default
+    if !io::stdin().is_terminal() {
+        println!();
+        bail!("Can't ask \"{}\" without a terminal, pass --yes or --no to answer it", question);
+    }
+    io::stdout().flush()?;
+    
+    let mut response = String::new();
+    io::stdin().read_line(&mut response)?;
+    let response = response.trim().to_lowercase();
+    
+    if response.is_empty() {
+        Ok(default_yes)
+    } else {
+        Ok(response == "y" || response == "yes")
+    }
+}
+
+fn check_dead_session(name: &str, config: &Config) -> Result<Option<SessionInfo>> {
+    // List all sessions including exited ones
+    let all_sessions = list_sessions(true, config)?;
+    
+    // Find a dead session with the given name
+    Ok(all_sessions.into_iter()
+        .find(|s| s.name == name && s.is_exited))
+}
+
+fn resurrect_dead_session(name: &str, config: &Config) -> Result<()> {
+    println!("{}: Resurrecting dead session '{}'", "Info".blue(), name.green());
+    
+    // Try to get the original working directory from the cached layout
+    let original_cwd = match load_cached_session_layout(name) {
+        Ok(layout) => get_layout_cwd(&layout),
+        Err(_) => None,
+    };
+    
+    // If we have an original cwd and it exists, use it for resurrection
+    let backend = config.backend();
+    let result = if let Some(cwd) = &original_cwd {
+        if Path::new(cwd).exists() {
+            println!("{}: Restoring session in original directory: {}", "Info".blue(), cwd.dimmed());
+            // Change to the original directory and resurrect
+            backend.attach(name, Some(cwd), config)
+        } else {
+            println!("{}: Original directory '{}' no longer exists, using current directory", "Warning".yellow(), cwd);
+            backend.attach(name, None, config)
+        }
+    } else {
+        // No cwd found, resurrect in current directory
+        backend.attach(name, None, config)
+    };
+    
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            // The attach might fail in non-terminal environments but still resurrect the session
+            // Check if the session is now active
+            let active_sessions = list_sessions(false, config)?;
+            if active_sessions.iter().any(|s| s.name == name && !s.is_exited) {
+                // Session was successfully resurrected despite the error
+                println!("{}: Session '{}' has been resurrected", "Success".green(), name.green());
+                if let Some(cwd) = original_cwd {
+                    println!("{}: Session restored in: {}", "Info".blue(), cwd.dimmed());
+                }
+                println!("Use '{}' to attach to it", format!("z {}", name).cyan());
+                Ok(())
+            } else {
+                // Session is still dead, offer to delete and recreate
+                println!("{}: Session appears to be corrupted.", "Warning".yellow());
+                if confirm("Would you like to delete it and create a new one?", true, config)? {
+                    // Delete the dead session
+                    println!("{}: Deleting dead session '{}'", "Info".blue(), name.yellow());
+                    backend.delete(name)
+                        .context("Failed to delete dead session")?;
+                    
+                    // Create a new session, optionally in original directory
+                    if let Some(cwd) = original_cwd {
+                        if Path::new(&cwd).exists() {
+                            println!("{}: Creating new session in original directory: {}", "Info".blue(), cwd.dimmed());
+                            create_session_with_cwd(name, &cwd, config)?;
+                        } else {
+                            create_session(name, config)?;
+                        }
+                    } else {
+                        create_session(name, config)?;
+                    }
+                } else {
+                    bail!("Session resurrection cancelled");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Non-interactive counterpart of `resurrect_dead_session`, for `--resurrect`
+fn resurrect_session(name: &str, config: &Config) -> Result<()> {
+    let all_sessions = list_sessions(true, config)?;
+    let session = all_sessions.iter().find(|s| s.name == name)
+        .with_context(|| format!("No session named '{}'", name))?;
+    if !session.is_exited {
+        println!("{}: Session '{}' is already running", "Info".blue(), name.green());
+        return Ok(());
+    }
+    
+    let original_cwd = resurrection_cwd(name);
+    
+    println!("{}: Resurrecting dead session '{}'", "Info".blue(), name.green());
+    let backend = config.backend();
+    let resurrected = match backend.resurrect(name, original_cwd.as_deref()) {
+        Ok(()) => wait_for_live_session(name, config),
+        Err(e) => {
+            eprintln!("{}: {}", "Warning".yellow(), e);
+            false
+        }
+    };
+    if resurrected {
+        println!("{}: Session '{}' has been resurrected", "Success".green(), name.green());
+        return Ok(());
+    }
+    
+    if !config.assume_yes {
+        bail!("Could not resurrect session '{}', pass --yes to delete and recreate it", name);
+    }
+    println!("{}: Deleting dead session '{}' and creating it again", "Info".blue(), name.yellow());
+    backend.delete(name)
+        .context("Failed to delete dead session")?;
+    backend.create(name, original_cwd.as_deref(), None, true, config)?;
+    if !wait_for_live_session(name, config) {
+        bail!("Recreated session '{}' did not come up", name);
+    }
+    println!("{}: Session '{}' has been recreated", "Success".green(), name.green());
+    Ok(())
+}
+
+/// Attaching from inside a session would nest clients, so bring the session back in the background first
+fn resurrect_for_switch(name: &str, config: &Config) -> Result<()> {
+    println!("{}: Session '{}' exists but is dead.", "Info".yellow(), name.cyan());
+    if !confirm("Would you like to resurrect it?", true, config)? {
+        bail!("Session resurrection cancelled");
+    }
+    
+    let original_cwd = resurrection_cwd(name);
+    println!("{}: Resurrecting dead session '{}'", "Info".blue(), name.green());
+    let backend = config.backend();
+    match backend.resurrect(name, original_cwd.as_deref()) {
+        Ok(()) if wait_for_live_session(name, config) => return Ok(()),
+        Ok(()) => {}
+        Err(e) => eprintln!("{}: {}", "Warning".yellow(), e),
+    }
+    
+    println!("{}: Session appears to be corrupted.", "Warning".yellow());
+    if !confirm("Would you like to delete it and create a new one?", true, config)? {
+        bail!("Session resurrection cancelled");
+    }
+    println!("{}: Deleting dead session '{}'", "Info".blue(), name.yellow());
+    backend.delete(name)
+        .context("Failed to delete dead session")?;
+    backend.create(name, original_cwd.as_deref(), None, true, config)?;
+    if !wait_for_live_session(name, config) {
+        bail!("Recreated session '{}' did not come up", name);
+    }
+    Ok(())
+}
+
+/// The directory an exited session was last working in, if it's still around
+fn resurrection_cwd(name: &str) -> Option<String> {
+    load_cached_session_layout(name).ok()
+        .and_then(|layout| get_layout_cwd(&layout))
+        .filter(|cwd| Path::new(cwd).exists())
+}
+
+/// Sessions started in the background take a moment to show up as running
+fn wait_for_live_session(name: &str, config: &Config) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(3);
+    loop {
+        let live = list_sessions(false, config)
+            .map(|sessions| sessions.iter().any(|s| s.name == name))
+            .unwrap_or(false);
+        if live || Instant::now() >= deadline {
+            return live;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Compact relative age using the two largest units, e.g. `3d 4h ago`
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    let units = [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)];
+    
+    let parts: Vec<String> = units.iter()
+        .scan(secs, |remaining, (suffix, unit)| {
+            let value = *remaining / unit;
+            *remaining %= unit;
+            Some((value, suffix))
+        })
+        .skip_while(|(value, _)| *value == 0)
+        .take(2)
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, suffix)| format!("{}{}", value, suffix))
+        .collect();
+    
+    if parts.is_empty() {
+        "just now".to_string()
+    } else {
+        format!("{} ago", parts.join(" "))
+    }
+}
+
+fn format_pane_count(count: usize) -> String {
+    match count {
+        0 => String::new(),
+        1 => " (1 pane)".to_string(),
+        n => format!(" ({} panes)", n),
+    }
+}
+
+/// Widths shared by every tab line in a listing so the columns line up
+#[derive(Default)]
+struct TabColumns {
+    label: usize,
+    command: usize,
+    /// Longest cwd that still fits on one line, if the terminal width is known
+    cwd: Option<usize>,
+}
+
+impl TabColumns {
+    fn measure<'a>(tabs: impl IntoIterator<Item = &'a TabInfo>, terminal_width: Option<usize>) -> Self {
+        let mut columns = TabColumns::default();
+        for tab in tabs {
+            columns.label = columns.label.max(tab_label(tab).chars().count());
+            for pane in &tab.panes {
+                let command = pane.command.as_deref().unwrap_or("-");
+                columns.command = columns.command.max(command.chars().count());
+            }
+        }
+        
+        // Indentation plus the spaces between columns; too narrow to help means no truncation
+        columns.cwd = terminal_width
+            .map(|width| width.saturating_sub(4 + columns.label + 1 + columns.command + 1))
+            .filter(|&width| width >= 10);
+        columns
+    }
+}
+
+fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width as usize)
+}
+
+/// Keep the end of the path, which is usually the part that tells directories apart
+fn truncate_start(text: &str, max_width: usize) -> Cow<'_, str> {
+    let width = text.chars().count();
+    if width <= max_width {
+        return Cow::Borrowed(text);
+    }
+    let tail: String = text.chars().skip(width - max_width.saturating_sub(1)).collect();
+    Cow::Owned(format!("…{}", tail))
+}
+
+fn pad(text: &str, width: usize) -> String {
+    " ".repeat(width.saturating_sub(text.chars().count()))
+}
+
+fn format_pane(pane: Option<&PaneInfo>) -> String {
+    format_pane_in_columns(pane, &TabColumns::default())
+}
+
+fn format_pane_in_columns(pane: Option<&PaneInfo>, columns: &TabColumns) -> String {
+    let cmd = pane.and_then(|p| p.command.as_deref()).unwrap_or("-");
+    let cwd = pane.and_then(|p| p.cwd.as_deref()).unwrap_or("-");
+    let floating = if pane.is_some_and(|p| p.floating) { " (floating)" } else { "" };
+    
+    // Deleted project directories leave sessions behind that are probably worth killing
+    let is_missing = pane.and_then(|p| p.cwd.as_deref())
+        .is_some_and(|cwd| Path::new(cwd).is_absolute() && !Path::new(cwd).exists());
+    let missing = if is_missing { " (missing)" } else { "" };
+    
+    let cwd = match columns.cwd {
+        Some(width) => truncate_start(cwd, width.saturating_sub(floating.len() + missing.len()).max(1)),
+        None => Cow::Borrowed(cwd),
+    };
+    format!("{}{} {}{}{}", 
+        paint(cmd, theme().command).dimmed(), 
+        pad(cmd, columns.command),
+        paint(&cwd, theme().cwd).dimmed(), 
+        missing.red(),
+        floating.dimmed())
+}
+
+fn tab_label(tab: &TabInfo) -> String {
+    let marker = if tab.focused { " •" } else { "" };
+    format!("{}{}{}", tab.name, format_pane_count(tab.pane_count), marker)
+}
+
+fn format_tab_line(tab: &TabInfo, columns: &TabColumns) -> String {
+    let label = format!("{}{}", tab.name, format_pane_count(tab.pane_count));
+    let marker = if tab.focused { " •" } else { "" };
+    let plain_label = tab_label(tab);
+    let width = columns.label.max(plain_label.chars().count());
+    let mut line = format!("{}{}{} {}", 
+        paint(&label, theme().tab).dimmed(), 
+        marker.bold(), 
+        pad(&plain_label, width),
+        format_pane_in_columns(tab.panes.first(), columns));
+    
+    if let Some(branch) = &tab.branch {
+        line.push_str(&format!(" {}", format!("({})", branch).magenta().dimmed()));
+    }
+    
+    // Further distinct panes go underneath, lined up with the first one
+    let padding = " ".repeat(width + 5);
+    for pane in tab.panes.iter().skip(1) {
+        line.push_str(&format!("\n{}{}", padding, format_pane_in_columns(Some(pane), columns)));
+    }
+    line
+}
+
+fn format_tabs(tabs_result: &Result<Vec<TabInfo>>, columns: &TabColumns) -> String {
+    match tabs_result {
+        Ok(tabs) => tabs.iter()
+            .map(|tab| format!("    {}\n", format_tab_line(tab, columns)))
+            .collect(),
+        Err(_) => format!("    {}\n", "[Unable to fetch tabs]".dimmed()),
+    }
+}
+
+fn format_tabs_tree(tabs_result: &Result<Vec<TabInfo>>) -> String {
+    let tabs = match tabs_result {
+        Ok(tabs) => tabs,
+        Err(_) => return format!("    {}\n", "[Unable to fetch tabs]".dimmed()),
+    };
     
-    // If we have an original cwd and it exists, use it for resurrection
-    let result = if let Some(cwd) = &original_cwd {
-        if Path::new(cwd).exists() {
-            println!("{}: Restoring session in original directory: {}", "Info".blue(), cwd.dimmed());
-            // Change to the original directory and resurrect
-            cmd!("zellij", "attach", name)
-                .dir(cwd)
-                .run()
-        } else {
-            println!("{}: Original directory '{}' no longer exists, using current directory", "Warning".yellow(), cwd);
-            cmd!("zellij", "attach", name)
-                .run()
+    let mut tree = String::new();
+    for (i, tab) in tabs.iter().enumerate() {
+        let last_tab = i == tabs.len() - 1;
+        let (branch, stem) = if last_tab { ("└─", "   ") } else { ("├─", "│  ") };
+        let marker = if tab.focused { " •" } else { "" };
+        tree.push_str(&format!("    {} {}{}{}\n",
+            branch.dimmed(), paint(&tab.name, theme().tab), format_pane_count(tab.pane_count).dimmed(), marker.bold()));
+        
+        for (j, pane) in tab.panes.iter().enumerate() {
+            let pane_branch = if j == tab.panes.len() - 1 { "└─" } else { "├─" };
+            tree.push_str(&format!("    {}{} {}\n",
+                stem.dimmed(), pane_branch.dimmed(), format_pane(Some(pane))));
+        }
+    }
+    tree
+}
+
+fn get_z_cache_dir() -> Option<PathBuf> {
+    if let Ok(cache_home) = env::var("XDG_CACHE_HOME")
+        && !cache_home.is_empty()
+    {
+        return Some(PathBuf::from(cache_home).join("z"));
+    }
+    let home = env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".cache").join("z"))
+}
+
+fn get_index_cache_path() -> Option<PathBuf> {
+    get_z_cache_dir().map(|dir| dir.join("index"))
+}
+
+/// Session names in the order listings show them, which is what `z <index>` counts through
+fn listing_order<T: AsRef<SessionInfo>>(sessions: &[T]) -> Vec<String> {
+    let mut sessions: Vec<&SessionInfo> = sessions.iter().map(|s| s.as_ref()).collect();
+    sessions.sort_by_key(|s| !s.meta.pinned);
+    sessions.into_iter().map(|s| s.name.clone()).collect()
+}
+
+/// Remember the order a listing showed, so `z <index>` refers to what was on screen
+fn write_index_cache<T: AsRef<SessionInfo>>(sessions: &[T]) {
+    let contents: String = listing_order(sessions).iter()
+        .map(|name| format!("{}\n", name))
+        .collect();
+    
+    let result = get_index_cache_path()
+        .context("Could not determine cache directory")
+        .and_then(|path| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, contents)?;
+            Ok(())
+        });
+    
+    if let Err(e) = result {
+        eprintln!("{}: Failed to save session order: {:#}", "Warning".yellow(), e);
+    }
+}
+
+fn read_index_cache() -> Option<Vec<String>> {
+    let contents = fs::read_to_string(get_index_cache_path()?).ok()?;
+    Some(contents.lines().map(|line| line.to_string()).collect())
+}
+
+fn resolve_index(query: &str, sessions: &[SessionInfo]) -> Result<Option<String>> {
+    resolve_index_in(query, sessions, read_index_cache())
+}
+
+/// Resolve against the order from the last listing, or the order a listing would show now
+fn resolve_index_in(query: &str, sessions: &[SessionInfo], cached: Option<Vec<String>>) -> Result<Option<String>> {
+    // A session literally named after the number takes precedence
+    if sessions.iter().any(|s| s.name == query) {
+        return Ok(None);
+    }
+    let index = match query.parse::<usize>() {
+        Ok(index) => index,
+        Err(_) => return Ok(None),
+    };
+    
+    let from_cache = cached.is_some();
+    let order = cached.unwrap_or_else(|| listing_order(sessions));
+    
+    // The listing shows both, so an all-digit hash prefix exactly as displayed wins over the index
+    let prefixes = find_shortest_prefixes(sessions);
+    if let Some((name, _)) = prefixes.iter().find(|(_, prefix)| prefix.as_str() == query) {
+        if index >= 1 && index <= order.len() {
+            eprintln!("{}: '{}' is both session {} in the listing and the hash prefix of '{}', going with the hash prefix", 
+                "Warning".yellow(), query, index, name);
         }
+        return Ok(None);
+    }
+    
+    if index == 0 || index > order.len() {
+        // Longer digit strings are more likely a hash prefix than a far-off index
+        if sessions.iter().any(|s| s.hash_prefix.starts_with(query)) {
+            return Ok(None);
+        }
+        bail!("Session index {} is out of range (1-{})", index, order.len());
+    }
+    
+    let name = &order[index - 1];
+    if from_cache && !sessions.iter().any(|s| s.name == *name) {
+        bail!("Session index {} is out of date ('{}' no longer exists), re-run z", index, name);
+    }
+    Ok(Some(name.clone()))
+}
+
+/// How long a parsed layout stays usable for repeated listings
+const LAYOUT_CACHE_TTL: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize)]
+struct CachedLayout {
+    /// Approximate creation time, so a recreated session with the same name isn't confused
+    created: Option<u64>,
+    written: u64,
+    tabs: Vec<TabInfo>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn session_created_at(session: &SessionInfo) -> Option<u64> {
+    // Ages are only minute-accurate, so round to keep the token stable between runs
+    session.created.map(|age| unix_now().saturating_sub(age.as_secs()) / 60)
+}
+
+fn get_layout_cache_path(session: &SessionInfo) -> Option<PathBuf> {
+    let key = blake3::hash(session.name.as_bytes()).to_hex();
+    get_z_cache_dir().map(|dir| dir.join("layouts").join(format!("{}.json", key)))
+}
+
+fn read_layout_cache(session: &SessionInfo) -> Option<Vec<TabInfo>> {
+    let contents = fs::read_to_string(get_layout_cache_path(session)?).ok()?;
+    let cached: CachedLayout = serde_json::from_str(&contents).ok()?;
+    
+    let fresh = unix_now().saturating_sub(cached.written) <= LAYOUT_CACHE_TTL.as_secs();
+    if fresh && cached.created == session_created_at(session) {
+        Some(cached.tabs)
     } else {
-        // No cwd found, resurrect in current directory
-        cmd!("zellij", "attach", name)
-            .run()
+        None
+    }
+}
+
+fn write_layout_cache(session: &SessionInfo, tabs: Vec<TabInfo>) -> Vec<TabInfo> {
+    let cached = CachedLayout {
+        created: session_created_at(session),
+        written: unix_now(),
+        tabs,
     };
     
-    match result {
-        Ok(_) => Ok(()),
-        Err(_) => {
-            // The attach might fail in non-terminal environments but still resurrect the session
-            // Check if the session is now active
-            let active_sessions = list_sessions(false)?;
-            if active_sessions.iter().any(|s| s.name == name && !s.is_exited) {
-                // Session was successfully resurrected despite the error
-                println!("{}: Session '{}' has been resurrected", "Success".green(), name.green());
-                if let Some(cwd) = original_cwd {
-                    println!("{}: Session restored in: {}", "Info".blue(), cwd.dimmed());
-                }
-                println!("Use '{}' to attach to it", format!("z {}", name).cyan());
-                Ok(())
-            } else {
-                // Session is still dead, offer to delete and recreate
-                println!("{}: Session appears to be corrupted.", "Warning".yellow());
-                print!("Would you like to delete it and create a new one? [Y/n] ");
-                io::stdout().flush()?;
-                
-                let mut response = String::new();
-                io::stdin().read_line(&mut response)?;
-                let response = response.trim().to_lowercase();
-                
-                if response.is_empty() || response == "y" || response == "yes" {
-                    // Delete the dead session
-                    println!("{}: Deleting dead session '{}'", "Info".blue(), name.yellow());
-                    cmd!("zellij", "delete-session", name)
-                        .run()
-                        .context("Failed to delete dead session")?;
-                    
-                    // Create a new session, optionally in original directory
-                    if let Some(cwd) = original_cwd {
-                        if Path::new(&cwd).exists() {
-                            println!("{}: Creating new session in original directory: {}", "Info".blue(), cwd.dimmed());
-                            create_session_with_cwd(name, &cwd)?;
-                        } else {
-                            create_session(name)?;
-                        }
-                    } else {
-                        create_session(name)?;
-                    }
-                } else {
-                    bail!("Session resurrection cancelled");
-                }
-                Ok(())
-            }
+    // Caching is best effort, a failed write just means the next run dumps again
+    if let (Some(path), Ok(contents)) = (get_layout_cache_path(session), serde_json::to_string(&cached)) {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
         }
+        let _ = fs::write(path, contents);
+    }
+    
+    cached.tabs
+}
+
+fn cached_session_tabs(session: &SessionInfo, config: &Config) -> Result<Vec<TabInfo>> {
+    // Exited sessions are already read from disk, only live dumps are worth caching
+    if !config.cache_layouts || session.is_exited {
+        return config.backend().session_tabs(session, config);
+    }
+    
+    if let Some(tabs) = read_layout_cache(session) {
+        return Ok(tabs);
+    }
+    
+    let tabs = config.backend().session_tabs(session, config)?;
+    Ok(write_layout_cache(session, tabs))
+}
+
+/// Each job is a subprocess, so too many at once just thrashes
+fn build_job_pool(jobs: Option<usize>) -> Result<rayon::ThreadPool> {
+    let jobs = jobs
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .max(1);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .context("Failed to start worker threads")
+}
+
+/// Fetch every session's tabs in parallel, keeping the sessions in their original order
+fn fetch_sessions_with_tabs(sessions: Vec<SessionInfo>, jobs: Option<usize>, config: &Config) -> Result<Vec<(SessionInfo, Result<Vec<TabInfo>>)>> {
+    let pool = build_job_pool(jobs)?;
+    Ok(pool.install(|| {
+        sessions
+            .into_par_iter()
+            .map(|session| {
+                let tabs = cached_session_tabs(&session, config);
+                (session, tabs)
+            })
+            .collect()
+    }))
+}
+
+fn git_branch(dir: &str) -> Option<String> {
+    cmd!("git", "-C", dir, "symbolic-ref", "--short", "HEAD")
+        .stderr_null()
+        .read()
+        .ok()
+        .map(|branch| branch.trim().to_string())
+        .filter(|branch| !branch.is_empty())
+}
+
+/// Look up each tab's branch, asking git only once per distinct directory
+fn add_git_branches(sessions_with_tabs: &mut [(SessionInfo, Result<Vec<TabInfo>>)], jobs: Option<usize>) -> Result<()> {
+    let mut dirs: Vec<String> = sessions_with_tabs.iter()
+        .filter_map(|(_, tabs)| tabs.as_ref().ok())
+        .flatten()
+        .filter_map(|tab| tab.cwd.clone())
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    
+    let pool = build_job_pool(jobs)?;
+    let branches: HashMap<String, String> = pool.install(|| {
+        dirs.into_par_iter()
+            .filter_map(|dir| git_branch(&dir).map(|branch| (dir, branch)))
+            .collect()
+    });
+    
+    for tab in sessions_with_tabs.iter_mut().filter_map(|(_, tabs)| tabs.as_mut().ok()).flatten() {
+        tab.branch = tab.cwd.as_ref()
+            .and_then(|cwd| branches.get(cwd).cloned());
     }
+    Ok(())
 }
 
-fn display_sessions_with_tabs(sessions_with_tabs: Vec<(SessionInfo, Result<Vec<TabInfo>>)>) -> Result<()> {
+fn display_sessions_with_tabs(sessions_with_tabs: Vec<(SessionInfo, Result<Vec<TabInfo>>)>, tree: bool, full_hash: bool) -> Result<()> {
     if sessions_with_tabs.is_empty() {
-        println!("{}", "No active zellij sessions found.".dimmed());
+        println!("{}", "No active sessions found.".dimmed());
         println!();
-        println!("Start a new session with: {}", "zellij".green());
-        println!("Start a named session with: {}", "zellij -s <name>".green());
+        println!("Start a named session with: {}", "z new <name>".green());
         return Ok(());
     }
     
     let sessions: Vec<&SessionInfo> = sessions_with_tabs.iter().map(|(s, _)| s).collect();
     let prefixes = find_shortest_prefixes(&sessions);
+    let columns = TabColumns::measure(
+        sessions_with_tabs.iter().filter_map(|(_, tabs)| tabs.as_ref().ok()).flatten(),
+        terminal_width());
+    let index_width = sessions.len().to_string().len();
     
     for (i, (session, tabs_result)) in sessions_with_tabs.iter().enumerate() {
-        let prefix = prefixes.get(&session.name).unwrap();
+        let prefix = if full_hash {
+            compute_hash_prefix(&session.name, HASH_HEX_LEN)
+        } else {
+            prefixes.get(&session.name).unwrap().clone()
+        };
+        let index = format!("{:>width$}", i + 1, width = index_width);
+        let age = session.created
+            .map(|created| format!(" {}", format_age(created)))
+            .unwrap_or_default();
+        let description = session.meta.description.as_ref()
+            .map(|description| format!(" {}", description))
+            .unwrap_or_default();
+        let pin = if session.meta.pinned { "★ " } else { "" };
         
+        let prefix = paint(&prefix, theme().prefix).bold();
         if session.is_current {
-            println!("{} {} {} {}", 
-                prefix.yellow().bold(),
-                "*".green().bold(), 
-                session.name.green().bold(), 
-                "(current)".dimmed()
+            println!("{} {} {} {}{}{} {}{}", 
+                index.dimmed(),
+                prefix,
+                paint("*", theme().current).bold(), 
+                pin.yellow(),
+                paint(&session.name, theme().current).bold(), 
+                description.dimmed(),
+                "(current)".dimmed(),
+                age.dimmed()
             );
         } else if session.is_exited {
-            println!("{} {} {}", 
-                prefix.yellow().bold(),
+            println!("{} {} {}{}{} {}{}", 
+                index.dimmed(),
+                prefix,
+                pin.yellow(),
                 session.name.red(),
-                "(EXITED)".red().dimmed()
+                description.dimmed(),
+                "(EXITED)".red().dimmed(),
+                age.dimmed()
             );
         } else {
-            println!("{} {}", 
-                prefix.yellow().bold(),
-                session.name.cyan()
+            println!("{} {} {}{}{}{}", 
+                index.dimmed(),
+                prefix,
+                pin.yellow(),
+                paint(&session.name, theme().name),
+                description.dimmed(),
+                age.dimmed()
             );
         }
         
         // Display tab information
+        if tree {
+            print!("{}", format_tabs_tree(tabs_result));
+        } else {
+            print!("{}", format_tabs(tabs_result, &columns));
+        }
+        
+        // Only add blank line between sessions, not after the last one
+        if i < sessions_with_tabs.len() - 1 {
+            println!();
+        }
+    }
+    
+    println!("\n{}: {}, {} or {} to attach", 
+        "Usage".yellow(), 
+        "z <session-name>".bold(),
+        "z <hash-prefix>".bold(),
+        "z <index>".bold()
+    );
+    Ok(())
+}
+
+fn watch_sessions(filter: Option<&regex::Regex>, interval: Duration, tree: bool, reverse: bool, jobs: Option<usize>, config: &Config) -> Result<()> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupted);
+    ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))
+        .context("Failed to install Ctrl-C handler")?;
+    
+    while !interrupted.load(Ordering::SeqCst) {
+        let mut sessions = list_sessions(config.include_exited, config)?;
+        sort_sessions(&mut sessions, config.sort, reverse);
+        if let Some(regex) = filter {
+            sessions.retain(|s| regex.is_match(&s.name));
+        }
+        let mut sessions_with_tabs = fetch_sessions_with_tabs(sessions, jobs, config)?;
+        sort_sessions_by_tabs(&mut sessions_with_tabs, config.sort, reverse);
+        
+        write_index_cache(&sessions_with_tabs.iter().map(|(s, _)| s).collect::<Vec<_>>());
+        
+        // Clear the screen and move the cursor home before drawing the next frame
+        print!("\x1b[2J\x1b[H");
+        display_sessions_with_tabs(sessions_with_tabs, tree, false)?;
+        io::stdout().flush()?;
+        
+        // Sleep in small steps so Ctrl-C doesn't have to wait out the whole interval
+        let deadline = Instant::now() + interval;
+        while Instant::now() < deadline && !interrupted.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+    
+    println!();
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SessionJson<'a> {
+    #[serde(flatten)]
+    session: &'a SessionInfo,
+    shortest_prefix: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    tabs: &'a [TabInfo],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tabs_error: Option<String>,
+}
+
+fn print_sessions_json(sessions_with_tabs: &[(SessionInfo, Result<Vec<TabInfo>>)], full_hash: bool) -> Result<()> {
+    println!("{}", sessions_json(sessions_with_tabs, full_hash)?);
+    Ok(())
+}
+
+fn sessions_json(sessions_with_tabs: &[(SessionInfo, Result<Vec<TabInfo>>)], full_hash: bool) -> Result<String> {
+    let sessions: Vec<&SessionInfo> = sessions_with_tabs.iter().map(|(s, _)| s).collect();
+    let prefixes = find_shortest_prefixes(&sessions);
+    
+    let entries: Vec<SessionJson> = sessions_with_tabs.iter()
+        .map(|(session, tabs_result)| {
+            let (tabs, tabs_error) = match tabs_result {
+                Ok(tabs) => (tabs.as_slice(), None),
+                Err(e) => (&[][..], Some(format!("{:#}", e))),
+            };
+            SessionJson {
+                session,
+                shortest_prefix: prefixes.get(&session.name).unwrap(),
+                // Unlike prefixes, the full hash doesn't change as other sessions come and go
+                hash: full_hash.then(|| compute_hash_prefix(&session.name, HASH_HEX_LEN)),
+                tabs,
+                tabs_error,
+            }
+        })
+        .collect();
+    
+    serde_json::to_string_pretty(&entries).context("Failed to serialize sessions")
+}
+
+/// One line per session, then `tab | command | cwd` per pane, with nothing decorative in between
+fn print_sessions_plain(sessions_with_tabs: &[(SessionInfo, Result<Vec<TabInfo>>)], full_hash: bool) {
+    let sessions: Vec<&SessionInfo> = sessions_with_tabs.iter().map(|(s, _)| s).collect();
+    let prefixes = find_shortest_prefixes(&sessions);
+    
+    for (session, tabs_result) in sessions_with_tabs {
+        let hash = if full_hash {
+            compute_hash_prefix(&session.name, HASH_HEX_LEN)
+        } else {
+            prefixes.get(&session.name).cloned().unwrap_or_default()
+        };
+        
+        let mut status = Vec::new();
+        if session.is_current {
+            status.push("current");
+        }
+        if session.is_exited {
+            status.push("exited");
+        }
+        if session.meta.pinned {
+            status.push("pinned");
+        }
+        let status = if status.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", status.join(", "))
+        };
+        let description = session.meta.description.as_deref()
+            .map(|d| format!(": {}", d))
+            .unwrap_or_default();
+        println!("{} {}{}{}", hash, session.name, status, description);
+        
         match tabs_result {
             Ok(tabs) => {
                 for tab in tabs {
-                    let cmd = tab.command.as_deref().unwrap_or("-");
-                    let cwd = tab.cwd.as_deref().unwrap_or("-");
-                    println!("    {} {} {}", 
-                        tab.name.dimmed(),
-                        cmd.blue().dimmed(),
-                        cwd.dimmed()
-                    );
+                    if tab.panes.is_empty() {
+                        println!("  {} | - | -", tab.name);
+                    }
+                    for pane in &tab.panes {
+                        println!("  {} | {} | {}", 
+                            tab.name,
+                            pane.command.as_deref().unwrap_or("-"),
+                            pane.cwd.as_deref().unwrap_or("-")
+                        );
+                    }
                 }
             }
-            Err(_) => {
-                println!("    {}", "[Unable to fetch tabs]".dimmed());
-            }
+            Err(e) => println!("  tabs unavailable: {:#}", e),
         }
+    }
+}
+
+struct PickerItem {
+    name: String,
+    label: String,
+    preview: String,
+}
+
+impl SkimItem for PickerItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.label)
+    }
+    
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.name)
+    }
+    
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        ItemPreview::AnsiText(self.preview.clone())
+    }
+}
+
+fn pick_session(sessions_with_tabs: &[(SessionInfo, Result<Vec<TabInfo>>)]) -> Result<Option<String>> {
+    let sessions: Vec<&SessionInfo> = sessions_with_tabs.iter().map(|(s, _)| s).collect();
+    let prefixes = find_shortest_prefixes(&sessions);
+    
+    let options = SkimOptionsBuilder::default()
+        .height(Some("50%"))
+        .preview(Some(""))
+        .preview_window(Some("right:60%"))
+        .prompt(Some("session> "))
+        .build()
+        .map_err(|e| anyhow!("Failed to configure session picker: {}", e))?;
+    
+    // Feed every session into the picker, labelled the same way as the listing
+    let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+    for (session, tabs_result) in sessions_with_tabs {
+        let prefix = prefixes.get(&session.name).unwrap();
+        let suffix = if session.is_current {
+            " (current)"
+        } else if session.is_exited {
+            " (EXITED)"
+        } else {
+            ""
+        };
+        let item = PickerItem {
+            name: session.name.clone(),
+            label: format!("{} {}{}", prefix, session.name, suffix),
+            preview: format_tabs(tabs_result, &TabColumns::measure(tabs_result.iter().flatten(), None)),
+        };
+        let _ = tx.send(Arc::new(item));
+    }
+    drop(tx);
+    
+    let selected = Skim::run_with(&options, Some(rx))
+        .filter(|output| !output.is_abort)
+        .and_then(|output| output.selected_items.first().map(|item| item.output().to_string()));
+    
+    Ok(selected)
+}
+
+fn get_state_dir() -> Option<PathBuf> {
+    if let Ok(state_home) = env::var("XDG_STATE_HOME")
+        && !state_home.is_empty()
+    {
+        return Some(PathBuf::from(state_home).join("z"));
+    }
+    let home = env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".local").join("state").join("z"))
+}
+
+fn get_last_session_path() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join("last"))
+}
+
+/// Most recently used session first, followed by the one used before it
+fn read_session_history() -> Vec<String> {
+    get_last_session_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|contents| {
+            contents.lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .take(2)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn record_session_switch(target: &str, current: Option<&str>) {
+    let history = read_session_history();
+    
+    // Whatever we're leaving becomes the previous session, so `z -` toggles back
+    let previous = match current {
+        Some(current) if current != target => Some(current.to_string()),
+        _ => history.into_iter().find(|name| name != target),
+    };
+    
+    let mut contents = format!("{}\n", target);
+    if let Some(previous) = previous {
+        contents.push_str(&format!("{}\n", previous));
+    }
+    
+    let result = get_last_session_path()
+        .context("Could not determine state directory")
+        .and_then(|path| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, contents)?;
+            Ok(())
+        });
+    
+    if let Err(e) = result {
+        eprintln!("{}: Failed to record last session: {:#}", "Warning".yellow(), e);
+    }
+}
+
+fn get_session_meta_dir() -> Option<PathBuf> {
+    get_state_dir().map(|dir| dir.join("sessions"))
+}
+
+fn get_session_meta_path(name: &str) -> Option<PathBuf> {
+    get_session_meta_dir().map(|dir| dir.join(format!("{}.kdl", name)))
+}
+
+fn parse_session_meta(contents: &str) -> Result<SessionMeta> {
+    let doc = contents.parse::<kdl::KdlDocument>()
+        .context("Failed to parse session metadata")?;
+    
+    let mut meta = SessionMeta::default();
+    for node in doc.nodes() {
+        let values: Vec<&kdl::KdlValue> = node.entries().iter()
+            .filter(|e| e.name().is_none())
+            .map(|e| e.value())
+            .collect();
         
-        // Only add blank line between sessions, not after the last one
-        if i < sessions_with_tabs.len() - 1 {
-            println!();
+        match node.name().value() {
+            "description" => meta.description = values.first().and_then(|v| v.as_string()).map(String::from),
+            "tags" => meta.tags = values.iter().filter_map(|v| v.as_string()).map(String::from).collect(),
+            "pinned" => meta.pinned = values.first().and_then(|v| v.as_bool()).unwrap_or(false),
+            _ => {}
+        }
+    }
+    Ok(meta)
+}
+
+fn load_session_meta(name: &str) -> SessionMeta {
+    let path = match get_session_meta_path(name) {
+        Some(path) if path.exists() => path,
+        _ => return SessionMeta::default(),
+    };
+    
+    let result = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {:?}", path))
+        .and_then(|contents| parse_session_meta(&contents));
+    
+    match result {
+        Ok(meta) => meta,
+        Err(e) => {
+            eprintln!("{}: {:#}, ignoring metadata for '{}'", "Warning".yellow(), e, name);
+            SessionMeta::default()
+        }
+    }
+}
+
+fn write_session_meta(name: &str, meta: &SessionMeta) -> Result<()> {
+    let path = get_session_meta_path(name)
+        .context("Could not determine state directory")?;
+    
+    // Nothing left to remember, so don't leave an empty file behind
+    if meta.description.is_none() && meta.tags.is_empty() && !meta.pinned {
+        remove_session_meta(name);
+        return Ok(());
+    }
+    
+    let mut contents = String::new();
+    if let Some(description) = &meta.description {
+        contents.push_str(&format!("description {}\n", kdl::KdlValue::String(description.clone())));
+    }
+    if !meta.tags.is_empty() {
+        let tags: Vec<String> = meta.tags.iter()
+            .map(|tag| kdl::KdlValue::String(tag.clone()).to_string())
+            .collect();
+        contents.push_str(&format!("tags {}\n", tags.join(" ")));
+    }
+    if meta.pinned {
+        contents.push_str("pinned true\n");
+    }
+    
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, contents)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+fn remove_session_meta(name: &str) {
+    if let Some(path) = get_session_meta_path(name) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Drop metadata left behind by sessions that no longer exist at all
+fn remove_stale_session_meta(sessions: &[SessionInfo]) -> usize {
+    let entries = match get_session_meta_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        Some(entries) => entries,
+        None => return 0,
+    };
+    
+    let mut removed = 0;
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let is_stale = path.extension().is_some_and(|e| e == "kdl")
+            && path.file_stem()
+                .is_some_and(|stem| !sessions.iter().any(|s| s.name == stem.to_string_lossy()));
+        if is_stale && fs::remove_file(&path).is_ok() {
+            removed += 1;
         }
     }
+    removed
+}
+
+fn annotate_session(query: &str, description: Option<String>, tags: Vec<String>, clear: bool, sessions: &[SessionInfo]) -> Result<()> {
+    let query = &resolve_index(query, sessions)?.unwrap_or_else(|| query.to_string());
+    let session = find_session(query, sessions)?
+        .context("No session found matching that name or hash prefix")?;
     
-    println!("\n{}: {} or {} to attach", 
-        "Usage".yellow(), 
-        "z <session-name>".bold(),
-        "z <hash-prefix>".bold()
-    );
+    let mut meta = load_session_meta(&session.name);
+    if clear {
+        meta.description = None;
+        meta.tags.clear();
+    }
+    if let Some(description) = description {
+        meta.description = Some(description).filter(|d| !d.is_empty());
+    }
+    if !tags.is_empty() {
+        meta.tags = tags;
+    }
+    write_session_meta(&session.name, &meta)?;
+    
+    println!("{}: Updated '{}'", "Success".green(), session.name.cyan());
+    Ok(())
+}
+
+fn set_session_pinned(query: &str, pinned: bool, sessions: &[SessionInfo]) -> Result<()> {
+    let query = &resolve_index(query, sessions)?.unwrap_or_else(|| query.to_string());
+    let session = find_session(query, sessions)?
+        .context("No session found matching that name or hash prefix")?;
+    
+    let mut meta = load_session_meta(&session.name);
+    meta.pinned = pinned;
+    write_session_meta(&session.name, &meta)?;
+    
+    let action = if pinned { "Pinned" } else { "Unpinned" };
+    println!("{}: {} '{}'", "Success".green(), action, session.name.cyan());
     Ok(())
 }
 
-fn attach_or_switch_session(name: &str, sessions: &[SessionInfo]) -> Result<()> {
-    // Check if we're already in a zellij session
-    if let Some(current) = get_current_session() {
-        // Find session by name or hash prefix
-        let session = sessions.iter()
-            .find(|s| s.name == name || s.hash_prefix.starts_with(name));
+fn resolve_last_session(config: &Config) -> Result<String> {
+    let history = read_session_history();
+    let current = config.backend().current_session();
+    
+    // Inside the most recent session, "last" means the one before it
+    let last = match (history.first(), current) {
+        (Some(recent), Some(current)) if *recent == current => history.get(1),
+        (recent, _) => recent,
+    };
+    
+    last.cloned()
+        .context("No previous session recorded yet")
+}
+
+fn attach_or_switch_session(name: &str, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    // A bare number refers to the position in the last listing
+    let name = &resolve_index(name, sessions)?.unwrap_or_else(|| name.to_string());
+    
+    // Check if we're already in a session
+    let backend = config.backend();
+    if let Some(current) = backend.current_session() {
+        // Exited sessions are usually left out of the listing, but switching to one should still work
+        let all_sessions;
+        let mut session = find_session(name, sessions)?;
+        if session.is_none() {
+            all_sessions = list_sessions(true, config)?;
+            session = find_session(name, &all_sessions)?;
+        }
         
         match session {
+            Some(target) if target.name == current => {
+                println!("{}: Already in session '{}'", "Info".blue(), current.yellow());
+            }
             Some(target) => {
-                if target.name == current {
-                    println!("{}: Already in session '{}'", "Info".blue(), current.yellow());
-                } else {
-                    // Switch to the target session
-                    println!("{}: Switching from '{}' to '{}'", 
-                        "Info".blue(), current.yellow(), target.name.green());
-                    cmd!("zellij", "action", "switch-session", &target.name)
-                        .run()
-                        .context("Failed to switch session")?;
+                if target.is_exited {
+                    resurrect_for_switch(&target.name, config)?;
                 }
+                
+                // Switch to the target session
+                println!("{}: Switching from '{}' to '{}'", 
+                    "Info".blue(), current.yellow(), target.name.green());
+                backend.switch(&target.name)
+                    .context("Failed to switch session")?;
+                record_session_switch(&target.name, Some(&current));
             }
             None => {
                 // Session doesn't exist, offer to create it
-                offer_to_create_session(name)?;
+                offer_to_create_session(name, config)?;
             }
         }
     } else {
         // Not in a session, try to attach
-        let session = sessions.iter()
-            .find(|s| s.name == name || s.hash_prefix.starts_with(name));
+        let session = find_session(name, sessions)?;
         
         match session {
             Some(target) => {
+                // Record before attaching, since attach only returns once we detach
+                record_session_switch(&target.name, None);
+                
                 // Attach to the session
-                cmd!("zellij", "attach", &target.name)
-                    .run()
-                    .context("Failed to attach to session")?;
+                backend.attach(&target.name, None, config)?;
             }
             None => {
                 // Session doesn't exist, offer to create it
-                offer_to_create_session(name)?;
+                offer_to_create_session(name, config)?;
             }
         }
     }
@@ -518,34 +2804,74 @@ fn attach_or_switch_session(name: &str, sessions: &[SessionInfo]) -> Result<()>
     Ok(())
 }
 
-fn offer_to_create_session(name: &str) -> Result<()> {
+/// Handles `session:tab` and `session:N`, focusing the tab before attaching so it's the first thing shown
+fn attach_or_switch_to_tab(target: &str, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    // A session literally named with a colon takes precedence
+    let (name, tab) = match target.split_once(':') {
+        Some((name, tab)) if !name.is_empty() && !tab.is_empty() && !sessions.iter().any(|s| s.name == target) => {
+            (name, Some(tab))
+        }
+        _ => (target, None),
+    };
+    
+    if let Some(tab) = tab {
+        let query = resolve_index(name, sessions)?.unwrap_or_else(|| name.to_string());
+        if let Some(session) = find_session(&query, sessions)? {
+            focus_session_tab(session, tab, config);
+        }
+    }
+    
+    attach_or_switch_session(name, sessions, config)
+}
+
+fn focus_session_tab(session: &SessionInfo, tab: &str, config: &Config) {
+    // Exited sessions have no live tabs to focus, so just attach to them
+    if session.is_exited {
+        return;
+    }
+    
+    let backend = config.backend();
+    let tabs = backend.session_tabs(session, config).unwrap_or_default();
+    
+    // Like session indices, a tab literally named after the number takes precedence
+    let result = match tab.parse::<usize>() {
+        Ok(index) if !tabs.iter().any(|t| t.name == tab) => {
+            if index == 0 || index > tabs.len() {
+                eprintln!("{}: Tab {} is out of range (1-{}), attaching anyway", 
+                    "Warning".yellow(), index, tabs.len());
+                return;
+            }
+            backend.focus_tab_index(&session.name, index)
+        }
+        _ => {
+            if !tabs.iter().any(|t| t.name == tab) {
+                eprintln!("{}: Session '{}' has no tab named '{}', attaching anyway", 
+                    "Warning".yellow(), session.name, tab);
+                return;
+            }
+            backend.focus_tab(&session.name, tab)
+        }
+    };
+    
+    if let Err(e) = result {
+        eprintln!("{}: Failed to focus tab '{}': {}", "Warning".yellow(), tab, e);
+    }
+}
+
+fn offer_to_create_session(name: &str, config: &Config) -> Result<()> {
     // First check if there's a dead session with this name
-    if let Some(_dead_session) = check_dead_session(name)? {
+    if let Some(_dead_session) = check_dead_session(name, config)? {
         println!("{}: Session '{}' exists but is dead.", "Info".yellow(), name.cyan());
-        print!("Would you like to resurrect it? [Y/n] ");
-        io::stdout().flush()?;
-        
-        let mut response = String::new();
-        io::stdin().read_line(&mut response)?;
-        let response = response.trim().to_lowercase();
-        
-        if response.is_empty() || response == "y" || response == "yes" {
-            resurrect_dead_session(name)?;
+        if confirm("Would you like to resurrect it?", true, config)? {
+            resurrect_dead_session(name, config)?;
         } else {
             println!("Session resurrection cancelled.");
         }
     } else {
         // No dead session found, offer to create a new one
         println!("{}: Session '{}' does not exist.", "Info".yellow(), name.cyan());
-        print!("Would you like to create it? [Y/n] ");
-        io::stdout().flush()?;
-        
-        let mut response = String::new();
-        io::stdin().read_line(&mut response)?;
-        let response = response.trim().to_lowercase();
-        
-        if response.is_empty() || response == "y" || response == "yes" {
-            create_session(name)?;
+        if confirm("Would you like to create it?", true, config)? {
+            create_session(name, config)?;
         } else {
             println!("Session creation cancelled.");
         }
@@ -554,123 +2880,981 @@ fn offer_to_create_session(name: &str) -> Result<()> {
     Ok(())
 }
 
-fn create_session(name: &str) -> Result<()> {
+fn session_create_args(name: &str, layout: Option<&Path>, config: &Config) -> Vec<String> {
+    let mut args = vec!["-s".to_string(), name.to_string()];
+    
+    if let Some(layout) = layout {
+        args.extend(["--layout".to_string(), layout.to_string_lossy().to_string()]);
+    }
+    
+    // Global zellij flags such as --config, which have to precede the `options` subcommand
+    args.extend(config.passthrough.iter().cloned());
+    
+    // Start the session with the configured shell rather than zellij's default
+    if let Some(shell) = &config.shell {
+        args.extend(["options".to_string(), "--default-shell".to_string(), shell.clone()]);
+    }
+    
+    args
+}
+
+/// Keep derived names shell- and zellij-friendly
+fn sanitize_session_name(raw: &str) -> String {
+    let name: String = raw.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '-' })
+        .collect();
+    name.trim_matches('-').to_string()
+}
+
+/// zellij keeps a socket per session, and long names overflow the socket path limit
+const MAX_SESSION_NAME_LEN: usize = 64;
+
+/// Tidy a name given on the command line into one zellij accepts, or explain why it can't be used
+fn validate_session_name(raw: &str) -> Result<String> {
+    let name: String = raw.trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .collect();
+    
+    if name.is_empty() {
+        bail!("Session name can't be empty");
+    }
+    if name.contains('/') || name.contains('\\') {
+        bail!("Session name '{}' can't contain slashes", name);
+    }
+    if name.chars().count() > MAX_SESSION_NAME_LEN {
+        bail!("Session name '{}' is too long (at most {} characters)", name, MAX_SESSION_NAME_LEN);
+    }
+    if name != raw {
+        println!("{}: Using '{}' as the session name", "Info".blue(), name.green());
+    }
+    Ok(name)
+}
+
+fn session_name_for_dir(dir: &Path) -> Result<String> {
+    let base = dir.file_name()
+        .context("Current directory has no name to derive a session name from")?
+        .to_string_lossy();
+    
+    let name = sanitize_session_name(&base);
+    if name.is_empty() {
+        bail!("Could not derive a session name from {:?}", dir);
+    }
+    Ok(name)
+}
+
+/// `npm run dev` started in ~/src/app becomes `app-npm`
+fn session_name_for_command(command: &str) -> Result<String> {
+    let program = command.split_whitespace().next()
+        .context("Command to run is empty")?;
+    let program = Path::new(program).file_name()
+        .map(|name| sanitize_session_name(&name.to_string_lossy()))
+        .unwrap_or_default();
+    
+    let dir_name = session_name_for_dir(&env::current_dir()?)?;
+    if program.is_empty() {
+        Ok(dir_name)
+    } else {
+        Ok(format!("{}-{}", dir_name, program))
+    }
+}
+
+fn same_dir(cwd: &str, target: &Path) -> bool {
+    let path = Path::new(cwd);
+    // Only left relative when the layout had no root cwd to resolve it against
+    if !path.is_absolute() {
+        return false;
+    }
+    path.canonicalize().is_ok_and(|path| path == target)
+}
+
+fn find_session_for_dir(dir: &Path, jobs: Option<usize>, sessions: &[SessionInfo], config: &Config) -> Result<Option<String>> {
+    let target = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    
+    // Same bounded, cached fetch as the listing, so this stays quick with many sessions
+    let live: Vec<SessionInfo> = sessions.iter().filter(|s| !s.is_exited).cloned().collect();
+    let sessions_with_tabs = fetch_sessions_with_tabs(live, jobs, config)?;
+    Ok(session_working_in(&target, &sessions_with_tabs))
+}
+
+/// First session with a pane in target, which should already be canonical
+fn session_working_in(target: &Path, sessions_with_tabs: &[(SessionInfo, Result<Vec<TabInfo>>)]) -> Option<String> {
+    sessions_with_tabs.iter()
+        .find(|(_, tabs)| tabs.iter()
+            .flatten()
+            .flat_map(|tab| &tab.panes)
+            .any(|pane| pane.cwd.as_deref().is_some_and(|cwd| same_dir(cwd, target))))
+        .map(|(session, _)| session.name.clone())
+}
+
+fn attach_or_create_here(jobs: Option<usize>, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    attach_or_create_for_dir(&env::current_dir()?, jobs, sessions, config)
+}
+
+fn attach_or_create_for_dir(dir: &Path, jobs: Option<usize>, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    if let Some(name) = find_session_for_dir(dir, jobs, sessions, config)? {
+        println!("{}: Session '{}' is already working in {}", 
+            "Info".blue(), name.green(), dir.display().to_string().dimmed());
+        return attach_or_switch_session(&name, sessions, config);
+    }
+    
+    let name = session_name_for_dir(dir)?;
+    if sessions.iter().any(|s| s.name == name) {
+        bail!("Session '{}' already exists but isn't working in {}. Use '{}' to pick another name.", 
+            name, dir.display(), "z new <name>");
+    }
+    create_session_with_cwd(&name, &dir.to_string_lossy(), config)
+}
+
+fn jump_to_session(query: &[String], jobs: Option<usize>, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    if find_in_path("zoxide").is_none() {
+        bail!("zoxide is not installed or not on your PATH. Install it from https://github.com/ajeetdsouza/zoxide.");
+    }
+    
+    let mut args = vec!["query".to_string(), "--".to_string()];
+    args.extend(query.iter().cloned());
+    let dir = cmd("zoxide", args)
+        .stderr_null()
+        .read()
+        .with_context(|| format!("zoxide has no directory matching '{}'", query.join(" ")))?;
+    
+    attach_or_create_for_dir(Path::new(dir.trim()), jobs, sessions, config)
+}
+
+fn create_session(name: &str, config: &Config) -> Result<()> {
+    let name = &validate_session_name(name)?;
     println!("{}: Creating session '{}'", "Info".blue(), name.green());
     
     // Check if we're already in a session
-    if get_current_session().is_some() {
-        // Create detached session
-        cmd!("zellij", "-s", name)
-            .stderr_null()
-            .stdout_null()
-            .start()?;
+    let backend = config.backend();
+    if backend.current_session().is_some() {
+        // Create detached session, starting here like an attached one would rather than wherever zellij's server is
+        let cwd = env::current_dir().context("Failed to determine the current directory")?;
+        backend.create(name, Some(&cwd.to_string_lossy()), None, true, config)?;
         println!("Session '{}' created. Use '{}' to switch to it.", 
             name.green(), format!("z {}", name).cyan());
     } else {
         // Create and attach
-        cmd!("zellij", "-s", name)
-            .run()
-            .context("Failed to create session")?;
+        backend.create(name, None, None, false, config)?;
+    }
+    
+    Ok(())
+}
+
+fn create_session_with_cwd(name: &str, cwd: &str, config: &Config) -> Result<()> {
+    let name = &validate_session_name(name)?;
+    println!("{}: Creating session '{}' in {}", "Info".blue(), name.green(), cwd.dimmed());
+    
+    // Check if we're already in a session
+    let backend = config.backend();
+    if backend.current_session().is_some() {
+        // Create detached session in specified directory
+        backend.create(name, Some(cwd), None, true, config)?;
+        println!("Session '{}' created. Use '{}' to switch to it.", 
+            name.green(), format!("z {}", name).cyan());
+    } else {
+        // Create and attach in specified directory
+        backend.create(name, Some(cwd), None, false, config)?;
+    }
+    
+    Ok(())
+}
+
+fn create_session_with_layout(name: &str, layout: &Path, cwd: Option<&str>, config: &Config) -> Result<()> {
+    let name = &validate_session_name(name)?;
+    println!("{}: Creating session '{}' from layout {}", 
+        "Info".blue(), name.green(), layout.display().to_string().dimmed());
+    
+    // Check if we're already in a session
+    let backend = config.backend();
+    if backend.current_session().is_some() {
+        // Create detached session with the layout applied, relative to the current directory unless told otherwise
+        let cwd = match cwd {
+            Some(cwd) => cwd.to_string(),
+            None => env::current_dir().context("Failed to determine the current directory")?.to_string_lossy().to_string(),
+        };
+        backend.create(name, Some(&cwd), Some(layout), true, config)?;
+        println!("Session '{}' created. Use '{}' to switch to it.", 
+            name.green(), format!("z {}", name).cyan());
+    } else {
+        // Create and attach with the layout applied
+        backend.create(name, cwd, Some(layout), false, config)?;
+    }
+    
+    Ok(())
+}
+
+fn run_in_new_session(command: &str, name: Option<String>, detach: bool, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    if command.trim().is_empty() {
+        bail!("Command to run is empty");
+    }
+    let name = match name {
+        Some(name) => validate_session_name(&name)?,
+        None => session_name_for_command(command)?,
+    };
+    if sessions.iter().any(|s| s.name == name) {
+        bail!("Session '{}' already exists, pick another with --name", name);
+    }
+    
+    println!("{}: Creating session '{}' running {}", "Info".blue(), name.green(), command.cyan());
+    
+    // Attaching from inside another session would nest them
+    let backend = config.backend();
+    if detach || backend.current_session().is_some() {
+        backend.create_running(&name, command, true, config)?;
+        println!("Session '{}' created. Use '{}' to switch to it.", 
+            name.green(), format!("z {}", name).cyan());
+    } else {
+        backend.create_running(&name, command, false, config)?;
+    }
+    
+    Ok(())
+}
+
+fn merge_sessions(source: &str, dest: &str, kill_source: bool, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    let source = &resolve_index(source, sessions)?.unwrap_or_else(|| source.to_string());
+    let dest = &resolve_index(dest, sessions)?.unwrap_or_else(|| dest.to_string());
+    let source = find_session(source, sessions)?
+        .context("No source session found matching that name or hash prefix")?;
+    let dest = find_session(dest, sessions)?
+        .context("No destination session found matching that name or hash prefix")?;
+    if source.name == dest.name {
+        bail!("Cannot merge session '{}' into itself", source.name);
+    }
+    if dest.is_exited {
+        bail!("Session '{}' has exited, attach to it first to resurrect it", dest.name);
+    }
+    
+    let backend = config.backend();
+    if kill_source && backend.current_session().as_deref() == Some(source.name.as_str()) {
+        bail!("Cannot kill the current session. Exit first or switch to another session.");
+    }
+    let tabs = backend.session_tabs(source, config)
+        .with_context(|| format!("Failed to read tabs of session '{}'", source.name))?;
+    
+    println!("{}: Merging {} tab(s) from '{}' into '{}'", 
+        "Info".blue(), tabs.len(), source.name.yellow(), dest.name.green());
+    println!("{}: Running programs can't be moved, their commands are started afresh", "Warning".yellow());
+    
+    // Replaying each tab's own layout keeps its splits, without one only the first pane can be recreated
+    let tab_layouts = backend.layout(source, config)
+        .and_then(|layout| split_layout_tabs(&layout))
+        .ok()
+        .filter(|layouts| layouts.len() == tabs.len());
+    if tab_layouts.is_none() {
+        println!("{}: No layout for '{}', only the first pane of each tab is recreated", "Warning".yellow(), source.name);
+    }
+    let merges_dir = get_z_cache_dir()
+        .context("Could not determine cache directory")?
+        .join("merges");
+    
+    for (i, tab) in tabs.iter().enumerate() {
+        let result = match &tab_layouts {
+            Some(layouts) => {
+                fs::create_dir_all(&merges_dir)?;
+                let layout_path = merges_dir.join(format!("{}-{}.kdl", dest.name, i + 1));
+                fs::write(&layout_path, &layouts[i])
+                    .with_context(|| format!("Failed to write layout to {:?}", layout_path))?;
+                backend.new_tab_from_layout(&dest.name, &tab.name, &layout_path)
+            }
+            None => {
+                let pane = tab.panes.first();
+                let cwd = pane.and_then(|p| p.cwd.as_deref());
+                let command = pane.and_then(|p| p.command.as_deref());
+                backend.new_tab(&dest.name, &tab.name, cwd, command)
+            }
+        };
+        result.with_context(|| format!("Failed to open tab '{}' in session '{}'", tab.name, dest.name))?;
+        println!("  {}", format_tab_line(tab, &TabColumns::default()));
+    }
+    
+    if kill_source && confirm(&format!("Kill session '{}'?", source.name), false, config)? {
+        backend.kill(&source.name)
+            .context("Failed to kill session")?;
+        println!("Session '{}' killed.", source.name.red());
+    }
+    
+    Ok(())
+}
+
+fn clone_session(source: &str, new_name: &str, config: &Config) -> Result<()> {
+    // Checked up front, since the name also becomes part of the layout's path
+    let new_name = &validate_session_name(new_name)?;
+    
+    // Exited sessions can be cloned too, from their cached layout
+    let mut sessions = list_sessions(true, config)?;
+    sort_sessions(&mut sessions, config.sort, false);
+    let source = &resolve_index(source, &sessions)?.unwrap_or_else(|| source.to_string());
+    let session = find_session(source, &sessions)?
+        .context("No session found matching that name or hash prefix")?;
+    
+    if sessions.iter().any(|s| s.name == *new_name) {
+        bail!("Session '{}' already exists", new_name);
+    }
+    
+    let layout = config.backend().layout(session, config)
+        .with_context(|| format!("Failed to read layout of session '{}'", session.name))?;
+    
+    // Kept on disk rather than in a temp file, since a detached zellij reads it asynchronously
+    let layout_path = get_z_cache_dir()
+        .context("Could not determine cache directory")?
+        .join("clones")
+        .join(format!("{}.kdl", new_name));
+    fs::create_dir_all(layout_path.parent().unwrap())?;
+    fs::write(&layout_path, layout)
+        .with_context(|| format!("Failed to write layout to {:?}", layout_path))?;
+    
+    println!("{}: Cloning session '{}' as '{}'", "Info".blue(), session.name.yellow(), new_name.green());
+    create_session_with_layout(new_name, &layout_path, None, config)
+}
+
+fn check_layout_file(path: &Path) -> Result<PathBuf> {
+    if !path.is_file() {
+        bail!("Layout file {:?} does not exist", path);
+    }
+    // Make sure we can actually read it before zellij tries to
+    fs::File::open(path)
+        .with_context(|| format!("Layout file {:?} is not readable", path))?;
+    
+    path.canonicalize()
+        .with_context(|| format!("Failed to resolve layout file {:?}", path))
+}
+
+fn check_cwd_dir(path: &Path) -> Result<PathBuf> {
+    if !path.is_dir() {
+        bail!("Directory {:?} does not exist", path);
+    }
+    path.canonicalize()
+        .with_context(|| format!("Failed to resolve directory {:?}", path))
+}
+
+fn get_templates_dir() -> Option<PathBuf> {
+    get_config_dir().map(|dir| dir.join("templates"))
+}
+
+fn available_templates() -> Result<Vec<String>> {
+    let mut templates = Vec::new();
+    
+    if let Some(dir) = get_templates_dir()
+        && dir.exists()
+    {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == "kdl")
+                && let Some(stem) = path.file_stem()
+            {
+                templates.push(stem.to_string_lossy().to_string());
+            }
+        }
+    }
+    
+    templates.sort();
+    Ok(templates)
+}
+
+fn resolve_template(template: &str) -> Result<PathBuf> {
+    let dir = get_templates_dir().context("Could not determine templates directory")?;
+    let path = dir.join(format!("{}.kdl", template));
+    
+    if path.is_file() {
+        return Ok(path);
+    }
+    
+    let templates = available_templates()?;
+    if templates.is_empty() {
+        bail!("Template '{}' not found. No templates exist in {:?}", template, dir);
+    }
+    println!("{}: Template '{}' not found. Available templates:", "Warning".yellow(), template);
+    for name in &templates {
+        println!("  {}", name.cyan());
+    }
+    bail!("Unknown template '{}'", template)
+}
+
+fn list_templates() -> Result<()> {
+    let templates = available_templates()?;
+    
+    if templates.is_empty() {
+        let dir = get_templates_dir().context("Could not determine templates directory")?;
+        println!("{}", format!("No templates found in {}", dir.display()).dimmed());
+        return Ok(());
+    }
+    
+    for name in templates {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+fn kill_session(name: &str, sessions: &[SessionInfo], force: bool, config: &Config) -> Result<()> {
+    // Find session by name, hash prefix or index
+    let name = &resolve_index(name, sessions)?.unwrap_or_else(|| name.to_string());
+    let session = find_session(name, sessions)?
+        .context("No session found matching that name or hash prefix")?;
+    
+    // Prevent killing current session
+    let backend = config.backend();
+    if let Some(current) = backend.current_session()
+        && session.name == current
+    {
+        bail!("Cannot kill the current session. Exit first or switch to another session.");
+    }
+    
+    // Prefixes can be ambiguous, so show exactly what's about to go
+    if !force {
+        let prefixes = find_shortest_prefixes(sessions);
+        println!("{} {}", 
+            prefixes.get(&session.name).unwrap().yellow().bold(),
+            session.name.red().bold()
+        );
+        let tabs = backend.session_tabs(session, config);
+        let columns = TabColumns::measure(tabs.iter().flatten(), terminal_width());
+        print!("{}", format_tabs(&tabs, &columns));
+        if !confirm(&format!("Kill session '{}'?", session.name), false, config)? {
+            println!("Kill cancelled.");
+            return Ok(());
+        }
+    }
+    
+    println!("{}: Killing session '{}'", "Info".blue(), session.name.red());
+    backend.kill(&session.name)
+        .context("Failed to kill session")?;
+    
+    println!("Session '{}' killed.", session.name.red());
+    Ok(())
+}
+
+/// Unlike `kill_session`, leaves nothing behind to resurrect
+fn delete_session(name: &str, force: bool, config: &Config) -> Result<()> {
+    // Exited sessions are the ones most likely to be deleted, so always look among them too
+    let mut all_sessions = list_sessions(true, config)?;
+    sort_sessions(&mut all_sessions, config.sort, false);
+    let name = &resolve_index(name, &all_sessions)?.unwrap_or_else(|| name.to_string());
+    let session = find_session(name, &all_sessions)?
+        .context("No session found matching that name or hash prefix")?;
+    
+    let backend = config.backend();
+    if backend.current_session().as_deref() == Some(session.name.as_str()) {
+        bail!("Cannot delete the current session. Exit first or switch to another session.");
+    }
+    
+    if !force {
+        let state = if session.is_exited { "exited" } else { "running" };
+        println!("{} {}", session.name.red().bold(), format!("({})", state).dimmed());
+        println!("{}", "This can't be undone: the session won't be resurrectable afterwards.".yellow());
+        if !confirm(&format!("Delete session '{}' for good?", session.name), false, config)? {
+            println!("Delete cancelled.");
+            return Ok(());
+        }
+    }
+    
+    if !session.is_exited {
+        println!("{}: Killing session '{}'", "Info".blue(), session.name.red());
+        backend.kill(&session.name)
+            .context("Failed to kill session")?;
+    }
+    println!("{}: Deleting session '{}'", "Info".blue(), session.name.red());
+    backend.delete(&session.name)
+        .context("Failed to delete session")?;
+    
+    println!("Session '{}' deleted.", session.name.red());
+    Ok(())
+}
+
+/// Expand the escapes people type on the command line, since shells pass `\n` through as is
+fn unescape_keys(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn send_to_session(query: &str, text: &str, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    let query = &resolve_index(query, sessions)?.unwrap_or_else(|| query.to_string());
+    let session = find_session(query, sessions)?
+        .context("No session found matching that name or hash prefix")?;
+    if session.is_exited {
+        bail!("Session '{}' has exited, attach to it first to resurrect it", session.name);
+    }
+    
+    config.backend().write_chars(&session.name, &unescape_keys(text))
+        .with_context(|| format!("Failed to send input to session '{}'", session.name))?;
+    println!("{}: Sent input to session '{}'", "Success".green(), session.name.cyan());
+    Ok(())
+}
+
+fn broadcast_to_sessions(text: &str, exclude_current: bool, jobs: Option<usize>, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    let targets: Vec<&SessionInfo> = sessions.iter()
+        .filter(|s| !s.is_exited && !(exclude_current && s.is_current))
+        .collect();
+    if targets.is_empty() {
+        println!("{}", "No live sessions to broadcast to.".dimmed());
+        return Ok(());
+    }
+    
+    let text = unescape_keys(text);
+    let backend = config.backend();
+    let pool = build_job_pool(jobs)?;
+    let results: Vec<(&SessionInfo, Result<()>)> = pool.install(|| {
+        targets
+            .into_par_iter()
+            .map(|session| (session, backend.write_chars(&session.name, &text)))
+            .collect()
+    });
+    
+    let mut failed = 0;
+    for (session, result) in &results {
+        match result {
+            Ok(()) => println!("  {} {}", "✓".green(), session.name.cyan()),
+            Err(e) => {
+                failed += 1;
+                println!("  {} {} {}", "✗".red(), session.name.red(), format!("({})", e).dimmed());
+            }
+        }
     }
     
+    println!("\n{}: Sent to {} of {} session(s)", 
+        if failed == 0 { "Success".green() } else { "Warning".yellow() },
+        results.len() - failed, results.len());
+    if failed > 0 {
+        bail!("Failed to send input to {} session(s)", failed);
+    }
     Ok(())
 }
 
-fn create_session_with_cwd(name: &str, cwd: &str) -> Result<()> {
-    println!("{}: Creating session '{}' in {}", "Info".blue(), name.green(), cwd.dimmed());
+/// Print every session with a pane accepted by `matches`, along with the tabs it was found in
+fn find_sessions_by_pane<F>(sessions: Vec<SessionInfo>, jobs: Option<usize>, config: &Config, matches: F) -> Result<()>
+where
+    F: Fn(&PaneInfo) -> bool,
+{
+    let sessions_with_tabs = fetch_sessions_with_tabs(sessions, jobs, config)?;
     
-    // Check if we're already in a session
-    if get_current_session().is_some() {
-        // Create detached session in specified directory
-        cmd!("zellij", "-s", name)
-            .dir(cwd)
-            .stderr_null()
-            .stdout_null()
-            .start()?;
-        println!("Session '{}' created. Use '{}' to switch to it.", 
-            name.green(), format!("z {}", name).cyan());
-    } else {
-        // Create and attach in specified directory
-        cmd!("zellij", "-s", name)
-            .dir(cwd)
-            .run()
-            .context("Failed to create session")?;
+    let mut found = false;
+    for (session, tabs_result) in &sessions_with_tabs {
+        let Ok(tabs) = tabs_result else { continue };
+        let matching: Vec<(&TabInfo, Vec<&PaneInfo>)> = tabs.iter()
+            .map(|tab| (tab, tab.panes.iter().filter(|pane| matches(pane)).collect::<Vec<_>>()))
+            .filter(|(_, panes)| !panes.is_empty())
+            .collect();
+        if matching.is_empty() {
+            continue;
+        }
+        
+        found = true;
+        let name = if session.is_exited { session.name.red() } else { session.name.cyan() };
+        println!("{}", name);
+        for (tab, panes) in matching {
+            for pane in panes {
+                println!("    {} {}", tab.name.dimmed(), format_pane(Some(pane)));
+            }
+        }
     }
     
+    if !found {
+        println!("{}", "No matching sessions found.".dimmed());
+    }
     Ok(())
 }
 
-fn kill_session(name: &str, sessions: &[SessionInfo]) -> Result<()> {
-    // Find session by name or hash prefix
-    let session = sessions.iter()
-        .find(|s| s.name == name || s.hash_prefix.starts_with(name))
-        .context("No session found matching that name or hash prefix")?;
-    
-    // Prevent killing current session
-    if let Some(current) = get_current_session() {
-        if session.name == current {
-            bail!("Cannot kill the current session. Exit first or switch to another session.");
+fn find_sessions_by_command(query: &str, case_sensitive: bool, jobs: Option<usize>, sessions: Vec<SessionInfo>, config: &Config) -> Result<()> {
+    let query = if case_sensitive { query.to_string() } else { query.to_lowercase() };
+    find_sessions_by_pane(sessions, jobs, config, |pane| {
+        pane.command.as_deref().is_some_and(|command| {
+            if case_sensitive {
+                command.contains(&query)
+            } else {
+                command.to_lowercase().contains(&query)
+            }
+        })
+    })
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
         }
+        _ => PathBuf::from(path),
     }
+}
+
+fn find_sessions_by_cwd(query: &str, jobs: Option<usize>, sessions: Vec<SessionInfo>, config: &Config) -> Result<()> {
+    // Existing directories match anything beneath them, anything else is a plain substring
+    match expand_tilde(query).canonicalize() {
+        Ok(dir) => find_sessions_by_pane(sessions, jobs, config, |pane| {
+            pane.cwd.as_deref()
+                .filter(|cwd| Path::new(cwd).is_absolute())
+                .is_some_and(|cwd| {
+                    let cwd = Path::new(cwd);
+                    cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf()).starts_with(&dir)
+                })
+        }),
+        Err(_) => find_sessions_by_pane(sessions, jobs, config, |pane| {
+            pane.cwd.as_deref().is_some_and(|cwd| cwd.contains(query))
+        }),
+    }
+}
+
+fn copy_session_name(query: &str, sessions: &[SessionInfo]) -> Result<()> {
+    let query = &resolve_index(query, sessions)?.unwrap_or_else(|| query.to_string());
+    let session = find_session(query, sessions)?
+        .context("No session found matching that name or hash prefix")?;
     
-    println!("{}: Killing session '{}'", "Info".blue(), session.name.red());
-    cmd!("zellij", "kill-session", &session.name)
-        .run()
-        .context("Failed to kill session")?;
-    
-    println!("Session '{}' killed.", session.name.red());
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(session.name.clone()))
+        .context("Failed to copy to the clipboard")?;
+    println!("{}: Copied '{}' to the clipboard", "Success".green(), session.name.cyan());
     Ok(())
 }
 
-fn rename_session(old_name: &str, new_name: &str, sessions: &[SessionInfo]) -> Result<()> {
-    // Find session by name or hash prefix
-    let session = sessions.iter()
-        .find(|s| s.name == old_name || s.hash_prefix.starts_with(old_name))
+fn detach_session(config: &Config) -> Result<()> {
+    let backend = config.backend();
+    if backend.current_session().is_none() {
+        bail!("Not inside a {} session, so there is nothing to detach from", backend.binary());
+    }
+    backend.detach()
+}
+
+fn rename_session(old_name: &str, new_name: &str, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    // Find session by name, hash prefix or index
+    let old_name = &resolve_index(old_name, sessions)?.unwrap_or_else(|| old_name.to_string());
+    let session = find_session(old_name, sessions)?
         .context("No session found matching that name or hash prefix")?;
     
+    let new_name = &validate_session_name(new_name)?;
+    if session.name == *new_name {
+        bail!("Session '{}' is already called that", session.name);
+    }
+    
     // Check if new name already exists
-    if sessions.iter().any(|s| s.name == new_name) {
+    if sessions.iter().any(|s| s.name == *new_name) {
         bail!("Session '{}' already exists", new_name);
     }
     
     println!("{}: Renaming session '{}' to '{}'", 
         "Info".blue(), session.name.yellow(), new_name.green());
     
-    // Check if we're renaming the current session
-    let in_current = get_current_session()
-        .map(|current| current == session.name)
-        .unwrap_or(false);
+    config.backend().rename(&session.name, new_name)
+        .context("Failed to rename session")?;
     
-    if in_current {
-        // Use action command when inside the session
-        cmd!("zellij", "action", "rename-session", new_name)
-            .run()
-            .context("Failed to rename session")?;
-    } else {
-        // Use regular command when outside
-        cmd!("zellij", "rename-session", &session.name, new_name)
-            .run()
-            .context("Failed to rename session")?;
+    // Annotations follow the session to its new name
+    if let (Some(old_path), Some(new_path)) = (get_session_meta_path(&session.name), get_session_meta_path(new_name))
+        && old_path.exists()
+    {
+        let _ = fs::rename(old_path, new_path);
     }
     
     println!("Session renamed successfully.");
     Ok(())
 }
 
-fn list_simple(sessions: &[SessionInfo]) -> Result<()> {
+fn prune_exited_sessions(config: &Config, older_than: Option<Duration>, dry_run: bool) -> Result<()> {
+    let backend = config.backend();
+    let current = backend.current_session();
+    let all_sessions = list_sessions(true, config)?;
+    
+    if !dry_run {
+        let removed = remove_stale_session_meta(&all_sessions);
+        if removed > 0 {
+            println!("{}", format!("Removed metadata of {} session(s) that no longer exist.", removed).dimmed());
+        }
+    }
+    
+    let exited: Vec<SessionInfo> = all_sessions
+        .into_iter()
+        .filter(|s| s.is_exited && current.as_ref() != Some(&s.name))
+        // Sessions of unknown age are kept, since we can't tell they're old enough
+        .filter(|s| older_than.is_none_or(|min_age| s.created.is_some_and(|age| age >= min_age)))
+        .collect();
+    
+    if exited.is_empty() {
+        println!("{}", "No exited sessions to prune.".dimmed());
+        return Ok(());
+    }
+    
+    println!("{}: {} exited session(s):", "Info".blue(), exited.len());
+    for session in &exited {
+        let age = session.created
+            .map(|created| format!(" {}", format_age(created)))
+            .unwrap_or_default();
+        println!("  {}{}", session.name.red(), age.dimmed());
+    }
+    
+    if dry_run {
+        println!("{}", "Dry run, nothing deleted.".dimmed());
+        return Ok(());
+    }
+    
+    if !confirm("Delete all of them?", false, config)? {
+        println!("Prune cancelled.");
+        return Ok(());
+    }
+    
+    for session in &exited {
+        backend.delete(&session.name)
+            .with_context(|| format!("Failed to delete session '{}'", session.name))?;
+        remove_session_meta(&session.name);
+        println!("Session '{}' deleted.", session.name.red());
+    }
+    
+    Ok(())
+}
+
+fn print_session_count(sessions: &[SessionInfo], json: bool) -> Result<()> {
+    if json {
+        let exited = sessions.iter().filter(|s| s.is_exited).count();
+        let counts = serde_json::json!({
+            "active": sessions.len() - exited,
+            "exited": exited,
+        });
+        println!("{}", counts);
+    } else {
+        println!("{}", sessions.len());
+    }
+    Ok(())
+}
+
+fn list_simple(sessions: &[SessionInfo], full_hash: bool, json: bool) -> Result<()> {
+    write_index_cache(sessions);
+    if json {
+        let names: Vec<_> = sessions.iter()
+            .map(|session| serde_json::json!({
+                "name": session.name,
+                "current": session.is_current,
+                "exited": session.is_exited,
+            }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&names)?);
+        return Ok(());
+    }
+    
     for session in sessions {
+        let hash = if full_hash {
+            format!("{} ", compute_hash_prefix(&session.name, HASH_HEX_LEN))
+        } else {
+            String::new()
+        };
         if session.is_current {
-            println!("{} {}", session.name, "(current)".dimmed());
+            println!("{}{} {}", hash, session.name, "(current)".dimmed());
         } else {
-            println!("{}", session.name);
+            println!("{}{}", hash, session.name);
         }
     }
     Ok(())
 }
 
+fn print_completion_script(shell: &str) -> Result<()> {
+    let mut command = Args::command();
+    // clap_complete's own shells don't include nushell, which lives in a separate crate
+    if shell == "nu" {
+        clap_complete::generate(clap_complete_nushell::Nushell, &mut command, "z", &mut io::stdout());
+        return Ok(());
+    }
+    
+    let shell: clap_complete::Shell = shell.parse()
+        .map_err(|e| anyhow!("Cannot generate completions: {}", e))?;
+    clap_complete::generate(shell, &mut command, "z", &mut io::stdout());
+    
+    // Session names aren't known to clap, so fish asks z for them at completion time
+    if shell == clap_complete::Shell::Fish {
+        println!("complete -c z -f -a \"(z --completions 2>/dev/null)\" -d \"Zellij session or hash prefix\"");
+    }
+    Ok(())
+}
+
+/// Create a session from a KDL layout piped in on stdin
+fn create_session_from_stdin(name: &str, cwd: Option<&str>, config: &Config) -> Result<()> {
+    let mut layout = String::new();
+    io::Read::read_to_string(&mut io::stdin(), &mut layout)
+        .context("Failed to read layout from stdin")?;
+    if layout.trim().is_empty() {
+        bail!("No layout was given on stdin");
+    }
+    if let Err(e) = layout.parse::<kdl::KdlDocument>() {
+        bail!("Layout from stdin is not valid KDL: {}", e);
+    }
+    
+    let runs_dir = get_z_cache_dir()
+        .context("Could not determine cache directory")?
+        .join("runs");
+    fs::create_dir_all(&runs_dir)
+        .with_context(|| format!("Failed to create {}", runs_dir.display()))?;
+    let layout_path = runs_dir.join(format!("{}.kdl", name));
+    fs::write(&layout_path, layout)
+        .with_context(|| format!("Failed to write {}", layout_path.display()))?;
+    
+    // A detached zellij reads the layout asynchronously, so only clean up once we've attached and come back
+    let detached = config.backend().current_session().is_some();
+    let result = create_session_with_layout(name, &layout_path, cwd, config);
+    if !detached {
+        let _ = fs::remove_file(&layout_path);
+    }
+    result
+}
+
+/// Create a session, named after the current directory unless told otherwise
+fn new_session(name: Option<String>, template: Option<&str>, layout: Option<&Path>, cwd: Option<&Path>, sessions: &[SessionInfo], config: &Config) -> Result<()> {
+    let cwd = cwd.map(check_cwd_dir).transpose()?;
+    let session_name = match name {
+        Some(name) => validate_session_name(&name)?,
+        None => {
+            let dir = match &cwd {
+                Some(cwd) => cwd.clone(),
+                None => env::current_dir()?,
+            };
+            let name = session_name_for_dir(&dir)?;
+            if sessions.iter().any(|s| s.name == name) {
+                println!("{}: Session '{}' already exists.", "Info".yellow(), name.cyan());
+                if confirm("Would you like to attach to it?", true, config)? {
+                    attach_or_switch_session(&name, sessions, config)?;
+                }
+                return Ok(());
+            }
+            name
+        }
+    };
+    
+    let cwd = cwd.as_ref().map(|cwd| cwd.to_string_lossy().to_string());
+    if let Some(template) = template {
+        let layout = resolve_template(template)?;
+        create_session_with_layout(&session_name, &layout, cwd.as_deref(), config)
+    } else if layout.is_some_and(|layout| layout == Path::new("-")) {
+        create_session_from_stdin(&session_name, cwd.as_deref(), config)
+    } else if let Some(layout) = layout {
+        let layout = check_layout_file(layout)?;
+        create_session_with_layout(&session_name, &layout, cwd.as_deref(), config)
+    } else if let Some(cwd) = &cwd {
+        create_session_with_cwd(&session_name, cwd, config)
+    } else {
+        create_session(&session_name, config)
+    }
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
-    let sessions = list_sessions(args.include_exited)?;
+    
+    // Needs to work before zellij is installed, so handle it before anything else
+    if let Some(shell) = &args.generate_completions {
+        return print_completion_script(shell);
+    }
+    if args.generate_man {
+        clap_mangen::Man::new(Args::command().name("z")).render(&mut io::stdout())?;
+        return Ok(());
+    }
+    apply_color_choice(args.color);
+    if args.plain {
+        colored::control::set_override(false);
+    }
+    
+    // Config provides defaults, CLI flags override them
+    let mut config = load_config();
+    let _ = THEME.set(config.theme.clone());
+    if args.include_exited {
+        config.include_exited = true;
+    }
+    if args.yes {
+        config.assume_yes = true;
+    }
+    if args.no {
+        config.assume_no = true;
+    }
+    config.backend = args.backend;
+    if args.no_cache {
+        config.cache_layouts = false;
+    }
+    if args.sort.is_some() {
+        config.sort = args.sort;
+    }
+    if let Some(hash_len) = args.hash_len {
+        config.hash_len = hash_len;
+    }
+    config.hash_len = config.hash_len.clamp(1, HASH_HEX_LEN);
+    config.passthrough = args.passthrough.clone();
+    
+    ensure_backend_installed(config.backend())?;
+    let mut sessions = list_sessions(config.include_exited, &config)?;
+    sort_sessions(&mut sessions, config.sort, args.reverse);
+    
+    let filter = args.filter.as_deref()
+        .map(|pattern| regex::Regex::new(pattern)
+            .with_context(|| format!("Invalid --filter pattern '{}'", pattern)))
+        .transpose()?;
+    if let Some(regex) = &filter {
+        sessions.retain(|s| regex.is_match(&s.name));
+    }
+    
+    if args.count {
+        return print_session_count(&sessions, args.json);
+    }
+    
+    if let Some(command) = args.command {
+        return match command {
+            Command::Ls => list_simple(&sessions, args.full_hash, args.json),
+            Command::New { name, template, layout, cwd, passthrough } => {
+                config.passthrough.extend(passthrough);
+                new_session(name, template.as_deref(), layout.as_deref(), cwd.as_deref(), &sessions, &config)
+            }
+            Command::Kill { session } => {
+                kill_session(&session, &sessions, args.force, &config)
+            }
+            Command::Rename { session, new_name } => {
+                rename_session(&session, &new_name, &sessions, &config)
+            }
+            Command::Attach { session, passthrough } => {
+                config.passthrough.extend(passthrough);
+                let session = if session == "-" {
+                    resolve_last_session(&config)?
+                } else {
+                    session
+                };
+                attach_or_switch_to_tab(&session, &sessions, &config)
+            }
+            Command::Run { command, name, detach } => {
+                run_in_new_session(&command, name, detach, &sessions, &config)
+            }
+            Command::Send { session, text } => {
+                send_to_session(&session, &text, &sessions, &config)
+            }
+            Command::Broadcast { text, exclude_current } => {
+                broadcast_to_sessions(&text, exclude_current, args.jobs, &sessions, &config)
+            }
+            Command::FindCmd { query, case_sensitive } => {
+                find_sessions_by_command(&query, case_sensitive, args.jobs, sessions, &config)
+            }
+            Command::Annotate { session, description, tags, clear } => {
+                annotate_session(&session, description, tags, clear, &sessions)
+            }
+            Command::Merge { source, dest, kill_source } => {
+                merge_sessions(&source, &dest, kill_source, &sessions, &config)
+            }
+            Command::Jump { query } => {
+                jump_to_session(&query, args.jobs, &sessions, &config)
+            }
+            Command::Open { dir } => {
+                attach_or_create_for_dir(&check_cwd_dir(&dir)?, args.jobs, &sessions, &config)
+            }
+            Command::FindCwd { query } => {
+                find_sessions_by_cwd(&query, args.jobs, sessions, &config)
+            }
+        };
+    }
     
     if args.completions {
         // Output just session names for completion
@@ -681,46 +3865,562 @@ fn main() -> Result<()> {
     }
     
     // Handle various operations
-    if args.list {
+    if args.list_templates {
+        list_templates()?;
+    } else if args.detach {
+        detach_session(&config)?;
+    } else if let Some(query) = &args.copy {
+        copy_session_name(query, &sessions)?;
+    } else if let Some(query) = &args.pin {
+        set_session_pinned(query, true, &sessions)?;
+    } else if let Some(query) = &args.unpin {
+        set_session_pinned(query, false, &sessions)?;
+    } else if let Some(name) = &args.resurrect {
+        resurrect_session(name, &config)?;
+    } else if let Some(query) = &args.delete {
+        delete_session(query, args.force, &config)?;
+    } else if args.here {
+        attach_or_create_here(args.jobs, &sessions, &config)?;
+    } else if args.prune || args.prune_older_than.is_some() {
+        prune_exited_sessions(&config, args.prune_older_than, args.dry_run)?;
+    } else if args.watch {
+        let interval = Duration::from_secs(args.interval.max(1));
+        watch_sessions(filter.as_ref(), interval, args.tree, args.reverse, args.jobs, &config)?;
+    } else if args.list {
         // Simple list mode
-        list_simple(&sessions)?;
+        list_simple(&sessions, args.full_hash, args.json)?;
     } else if args.new {
-        // Create new session
-        let session_name = args.session
-            .context("Session name required for --new flag")?;
-        create_session(&session_name)?;
+        new_session(args.session, args.template.as_deref(), args.layout.as_deref(), args.cwd.as_deref(), &sessions, &config)?;
     } else if args.kill {
         // Kill session
         let session_name = args.session
             .context("Session name required for --kill flag")?;
-        kill_session(&session_name, &sessions)?;
+        kill_session(&session_name, &sessions, args.force, &config)?;
+    } else if args.clone {
+        let source = args.session
+            .context("Source session name required for --clone flag")?;
+        let new_name = args.new_name
+            .context("New session name required for --clone flag")?;
+        clone_session(&source, &new_name, &config)?;
     } else if args.rename {
         // Rename session
         let old_name = args.session
             .context("Old session name required for --rename flag")?;
         let new_name = args.new_name
             .context("New session name required for --rename flag")?;
-        rename_session(&old_name, &new_name, &sessions)?;
+        rename_session(&old_name, &new_name, &sessions, &config)?;
     } else {
         // Default behavior: attach/switch or display
-        match args.session {
+        let session = if args.last || args.session.as_deref() == Some("-") {
+            Some(resolve_last_session(&config)?)
+        } else {
+            args.session
+        };
+        
+        // Only stands in for the picker, never for output that was explicitly asked for
+        let bare_listing = !args.json
+            && !args.plain
+            && !args.tree
+            && !args.no_picker
+            && !args.git
+            && io::stdout().is_terminal();
+        
+        match session {
             Some(session_name) => {
-                attach_or_switch_session(&session_name, &sessions)?;
+                attach_or_switch_to_tab(&session_name, &sessions, &config)?;
+            }
+            None if config.auto_attach && bare_listing && sessions.len() == 1 => {
+                // Only one session to choose from, so skip the listing entirely
+                let name = sessions[0].name.clone();
+                attach_or_switch_session(&name, &sessions, &config)?;
             }
             None => {
                 // Fetch tab information in parallel
-                let sessions_with_tabs: Vec<(SessionInfo, Result<Vec<TabInfo>>)> = sessions
-                    .into_par_iter()
-                    .map(|session| {
-                        let tabs = parse_session_tabs(&session);
-                        (session, tabs)
-                    })
-                    .collect();
-                    
-                display_sessions_with_tabs(sessions_with_tabs)?;
+                let mut sessions_with_tabs = fetch_sessions_with_tabs(sessions, args.jobs, &config)?;
+                sort_sessions_by_tabs(&mut sessions_with_tabs, config.sort, args.reverse);
+                if args.git {
+                    add_git_branches(&mut sessions_with_tabs, args.jobs)?;
+                }
+                
+                // Every listing below shows this order, so `z <index>` counts through it
+                write_index_cache(&sessions_with_tabs.iter().map(|(s, _)| s).collect::<Vec<_>>());
+                
+                if args.json {
+                    return print_sessions_json(&sessions_with_tabs, args.full_hash);
+                }
+                if args.plain {
+                    print_sessions_plain(&sessions_with_tabs, args.full_hash);
+                    return Ok(());
+                }
+                
+                // Only offer the picker when a human is looking at the output
+                let use_picker = !args.no_picker
+                    && !args.tree
+                    && io::stdout().is_terminal()
+                    && !sessions_with_tabs.is_empty();
+                
+                if use_picker {
+                    if let Some(name) = pick_session(&sessions_with_tabs)? {
+                        let sessions: Vec<SessionInfo> = sessions_with_tabs
+                            .into_iter()
+                            .map(|(session, _)| session)
+                            .collect();
+                        attach_or_switch_session(&name, &sessions, &config)?;
+                    }
+                } else {
+                    display_sessions_with_tabs(sessions_with_tabs, args.tree, args.full_hash)?;
+                }
             }
         }
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_plain_session_list() {
+        let output = "alpha [Created 2h 3m ago] (current)\n\
+                      beta [Created 1day ago]\n\
+                      gamma [Created 3days ago] (EXITED - attach to resurrect)\n";
+        let sessions = parse_zellij_sessions(output);
+        
+        let names: Vec<&str> = sessions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["alpha", "beta", "gamma"]);
+        
+        assert!(sessions[0].is_current && !sessions[0].is_exited);
+        assert_eq!(sessions[0].created, Some(Duration::from_secs(2 * 60 * 60 + 3 * 60)));
+        
+        assert!(!sessions[1].is_current && !sessions[1].is_exited);
+        assert_eq!(sessions[1].created, Some(Duration::from_secs(24 * 60 * 60)));
+        
+        assert!(!sessions[2].is_current && sessions[2].is_exited);
+        assert_eq!(sessions[2].created, Some(Duration::from_secs(3 * 24 * 60 * 60)));
+    }
+    
+    #[test]
+    fn parses_colored_session_list_like_plain() {
+        let colored = "\x1b[32;1malpha\x1b[m [Created \x1b[35;1m2h 3m\x1b[m ago] (\x1b[32;1mcurrent\x1b[m)\n\
+                       \x1b[32;1mgamma\x1b[m [Created \x1b[35;1m3days\x1b[m ago] (\x1b[31;1mEXITED\x1b[m - attach to resurrect)\n";
+        let plain = "alpha [Created 2h 3m ago] (current)\n\
+                     gamma [Created 3days ago] (EXITED - attach to resurrect)\n";
+        
+        let from_colored = parse_zellij_sessions(colored);
+        let from_plain = parse_zellij_sessions(plain);
+        assert_eq!(from_colored.len(), 2);
+        for (colored, plain) in from_colored.iter().zip(&from_plain) {
+            assert_eq!(colored.name, plain.name);
+            assert_eq!(colored.is_current, plain.is_current);
+            assert_eq!(colored.is_exited, plain.is_exited);
+            assert_eq!(colored.created, plain.created);
+        }
+    }
+    
+    #[test]
+    fn skips_blank_lines_and_keeps_bare_names() {
+        let sessions = parse_zellij_sessions("\nsolo\n   \n");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].name, "solo");
+        assert_eq!(sessions[0].created, None);
+    }
+    
+    fn tiled(command: Option<&str>, cwd: &str) -> PaneInfo {
+        PaneInfo { command: command.map(String::from), cwd: Some(cwd.to_string()), floating: false }
+    }
+    
+    #[test]
+    fn recovers_panes_from_nested_splits() {
+        let layout = r#"
+            layout {
+                cwd "/home/me"
+                tab name="code" focus=true {
+                    pane split_direction="vertical" {
+                        pane command="nvim" cwd="src/app" focus=true
+                        pane split_direction="horizontal" {
+                            pane command="cargo" cwd="src/app" {
+                                args "watch"
+                            }
+                            pane cwd="/tmp"
+                        }
+                    }
+                }
+                tab name="logs" cwd="/var/log" {
+                    pane command="tail"
+                }
+            }
+        "#;
+        let tabs = parse_kdl_layout(layout).unwrap();
+        assert_eq!(tabs.len(), 2);
+        
+        let code = &tabs[0];
+        assert_eq!(code.name, "code");
+        assert!(code.focused);
+        assert_eq!(code.pane_count, 3);
+        assert_eq!(code.panes, [
+            tiled(Some("nvim"), "/home/me/src/app"),
+            tiled(Some("cargo"), "/home/me/src/app"),
+            tiled(None, "/tmp"),
+        ]);
+        assert_eq!(code.cwd.as_deref(), Some("/home/me/src/app"));
+        
+        let logs = &tabs[1];
+        assert_eq!(logs.name, "logs");
+        assert!(!logs.focused);
+        assert_eq!(logs.panes, [tiled(Some("tail"), "/var/log")]);
+        assert_eq!(logs.cwd.as_deref(), Some("/var/log"));
+    }
+    
+    #[test]
+    fn collects_every_member_of_a_stack() {
+        let layout = r#"
+            layout {
+                tab name="stacked" cwd="/srv" {
+                    pane stacked=true {
+                        pane command="htop"
+                        pane command="cargo" cwd="api" {
+                            args "run"
+                        }
+                        pane command="cargo" cwd="api" {
+                            args "run"
+                        }
+                        pane cwd="web" focus=true
+                    }
+                }
+            }
+        "#;
+        let tabs = parse_kdl_layout(layout).unwrap();
+        assert_eq!(tabs.len(), 1);
+        
+        let tab = &tabs[0];
+        // Identical members are collapsed in the list but still counted
+        assert_eq!(tab.pane_count, 4);
+        assert_eq!(tab.panes, [
+            tiled(Some("htop"), "/srv"),
+            tiled(Some("cargo"), "/srv/api"),
+            tiled(None, "/srv/web"),
+        ]);
+        assert_eq!(tab.cwd.as_deref(), Some("/srv/web"));
+    }
+    
+    #[test]
+    fn skips_plugin_panes() {
+        let layout = r#"
+            layout {
+                tab name="main" {
+                    pane size=1 borderless=true {
+                        plugin location="zellij:tab-bar"
+                    }
+                    pane command="bash" cwd="/home/me"
+                    pane size=2 borderless=true plugin="zellij:status-bar"
+                }
+            }
+        "#;
+        let tabs = parse_kdl_layout(layout).unwrap();
+        assert_eq!(tabs.len(), 1);
+        assert_eq!(tabs[0].pane_count, 1);
+        assert_eq!(tabs[0].panes, [tiled(Some("bash"), "/home/me")]);
+    }
+    
+    fn session_with_hash(name: &str, hash_prefix: &str) -> SessionInfo {
+        SessionInfo {
+            name: name.to_string(),
+            is_current: false,
+            is_exited: false,
+            hash_prefix: hash_prefix.to_string(),
+            created: None,
+            meta: SessionMeta::default(),
+        }
+    }
+    
+    /// The shortest prefix of each hash that no other hash starts with, checked against every other session
+    fn naive_shortest_prefixes(sessions: &[SessionInfo]) -> HashMap<String, String> {
+        sessions.iter()
+            .map(|session| {
+                let hash = &session.hash_prefix;
+                let len = (1..=hash.len())
+                    .find(|&len| !sessions.iter()
+                        .any(|other| other.name != session.name && other.hash_prefix.starts_with(&hash[..len])))
+                    .unwrap_or(hash.len());
+                (session.name.clone(), hash[..len].to_string())
+            })
+            .collect()
+    }
+    
+    /// Deterministic stand-in for a random number generator, so failures can be reproduced
+    struct Lcg(u64);
+    
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % bound as u64) as usize
+        }
+    }
+    
+    #[test]
+    fn shortest_prefixes_match_naive_on_real_hashes() {
+        let mut rng = Lcg(42);
+        for round in 0..200 {
+            let hash_len = 1 + rng.next(6);
+            let sessions: Vec<SessionInfo> = (0..rng.next(40))
+                .map(|i| {
+                    let name = format!("session-{}-{}", round, i);
+                    let hash = compute_hash_prefix(&name, hash_len);
+                    session_with_hash(&name, &hash)
+                })
+                .collect();
+            assert_eq!(find_shortest_prefixes(&sessions), naive_shortest_prefixes(&sessions), "round {}", round);
+        }
+    }
+    
+    #[test]
+    fn shortest_prefixes_match_naive_on_crowded_hashes() {
+        // A two-letter alphabet makes long shared prefixes and outright duplicates common
+        let mut rng = Lcg(7);
+        for round in 0..200 {
+            let hash_len = 1 + rng.next(8);
+            let sessions: Vec<SessionInfo> = (0..rng.next(30))
+                .map(|i| {
+                    let hash: String = (0..hash_len).map(|_| if rng.next(2) == 0 { 'a' } else { 'b' }).collect();
+                    session_with_hash(&format!("s{}", i), &hash)
+                })
+                .collect();
+            assert_eq!(find_shortest_prefixes(&sessions), naive_shortest_prefixes(&sessions), "round {}", round);
+        }
+    }
+    
+    #[test]
+    fn shortest_prefixes_fall_back_to_the_whole_hash() {
+        // Identical hashes can't be told apart, so both get all of theirs
+        let sessions = [session_with_hash("one", "abc"), session_with_hash("two", "abc"), session_with_hash("three", "abd")];
+        let prefixes = find_shortest_prefixes(&sessions);
+        assert_eq!(prefixes["one"], "abc");
+        assert_eq!(prefixes["two"], "abc");
+        assert_eq!(prefixes["three"], "abd");
+        
+        let sessions = [session_with_hash("only", "f00d")];
+        assert_eq!(find_shortest_prefixes(&sessions)["only"], "f");
+    }
+    
+    #[test]
+    fn shortest_prefixes_never_exceed_hash_len() {
+        assert_eq!(compute_hash_prefix("alpha", 0).len(), 1);
+        assert_eq!(compute_hash_prefix("alpha", 1000).len(), HASH_HEX_LEN);
+        
+        // With one-character hashes, collisions are certain among 17 sessions
+        let sessions: Vec<SessionInfo> = (0..17)
+            .map(|i| {
+                let name = format!("s{}", i);
+                let hash = compute_hash_prefix(&name, 1);
+                session_with_hash(&name, &hash)
+            })
+            .collect();
+        for (name, prefix) in find_shortest_prefixes(&sessions) {
+            let session = sessions.iter().find(|s| s.name == name).unwrap();
+            assert_eq!(prefix, session.hash_prefix);
+        }
+    }
+    
+    fn pinned(mut session: SessionInfo) -> SessionInfo {
+        session.meta.pinned = true;
+        session
+    }
+    
+    fn names<T: AsRef<SessionInfo>>(sessions: &[T]) -> Vec<&str> {
+        sessions.iter().map(|s| s.as_ref().name.as_str()).collect()
+    }
+    
+    #[test]
+    fn pinned_sessions_sort_first() {
+        let unsorted = || vec![
+            session_with_hash("bravo", "1"),
+            pinned(session_with_hash("delta", "2")),
+            session_with_hash("charlie", "3"),
+            pinned(session_with_hash("alpha", "4")),
+        ];
+        
+        let mut sessions = unsorted();
+        sort_sessions(&mut sessions, None, false);
+        assert_eq!(names(&sessions), ["delta", "alpha", "bravo", "charlie"]);
+        
+        let mut sessions = unsorted();
+        sort_sessions(&mut sessions, Some(SortKey::Name), false);
+        assert_eq!(names(&sessions), ["alpha", "delta", "bravo", "charlie"]);
+        
+        // Reversing flips the order within each group, not the groups themselves
+        let mut sessions = unsorted();
+        sort_sessions(&mut sessions, Some(SortKey::Name), true);
+        assert_eq!(names(&sessions), ["delta", "alpha", "charlie", "bravo"]);
+    }
+    
+    #[test]
+    fn pinned_sessions_sort_first_by_tab_count() {
+        let with_tabs = |session: SessionInfo, count: usize| {
+            let tabs = (0..count)
+                .map(|i| TabInfo { name: i.to_string(), panes: Vec::new(), pane_count: 0, focused: false, command: None, cwd: None, branch: None })
+                .collect();
+            (session, Ok(tabs))
+        };
+        let mut sessions_with_tabs = vec![
+            with_tabs(session_with_hash("few", "1"), 1),
+            with_tabs(pinned(session_with_hash("pinned", "2")), 2),
+            with_tabs(session_with_hash("many", "3"), 5),
+        ];
+        sort_sessions_by_tabs(&mut sessions_with_tabs, Some(SortKey::Tabs), false);
+        let sessions: Vec<&SessionInfo> = sessions_with_tabs.iter().map(|(s, _)| s).collect();
+        assert_eq!(names(&sessions), ["pinned", "many", "few"]);
+    }
+    
+    fn cached(names: &[&str]) -> Option<Vec<String>> {
+        Some(names.iter().map(|name| name.to_string()).collect())
+    }
+    
+    #[test]
+    fn index_counts_through_the_cached_listing() {
+        let sessions = [session_with_hash("alpha", "aa"), session_with_hash("beta", "bb"), session_with_hash("gamma", "cc")];
+        let order = cached(&["gamma", "alpha", "beta"]);
+        
+        assert_eq!(resolve_index_in("1", &sessions, order.clone()).unwrap().as_deref(), Some("gamma"));
+        assert_eq!(resolve_index_in("3", &sessions, order.clone()).unwrap().as_deref(), Some("beta"));
+        assert!(resolve_index_in("4", &sessions, order.clone()).is_err());
+        assert!(resolve_index_in("0", &sessions, order.clone()).is_err());
+        // Not a number, so left for the name and hash prefix lookup
+        assert_eq!(resolve_index_in("beta", &sessions, order).unwrap(), None);
+    }
+    
+    #[test]
+    fn index_without_a_cache_follows_the_listing_order() {
+        // Pinned sessions are listed first, so index 1 has to be the pinned one too
+        let sessions = [session_with_hash("alpha", "aa"), pinned(session_with_hash("beta", "bb"))];
+        assert_eq!(resolve_index_in("1", &sessions, None).unwrap().as_deref(), Some("beta"));
+        assert_eq!(resolve_index_in("2", &sessions, None).unwrap().as_deref(), Some("alpha"));
+    }
+    
+    #[test]
+    fn index_rejects_a_stale_cache() {
+        let sessions = [session_with_hash("alpha", "aa")];
+        let error = resolve_index_in("1", &sessions, cached(&["gone", "alpha"])).unwrap_err();
+        assert!(error.to_string().contains("out of date"), "{}", error);
+    }
+    
+    #[test]
+    fn index_gives_way_to_names_and_displayed_prefixes() {
+        let sessions = [session_with_hash("2", "aa"), session_with_hash("beta", "31"), session_with_hash("gamma", "cc")];
+        let order = cached(&["gamma", "beta", "2"]);
+        
+        // A session named "2" beats the second row
+        assert_eq!(resolve_index_in("2", &sessions, order.clone()).unwrap(), None);
+        // "3" is beta's displayed prefix as well as the third row
+        assert_eq!(resolve_index_in("3", &sessions, order.clone()).unwrap(), None);
+        // Past the end of the listing, but the start of a hash
+        assert_eq!(resolve_index_in("31", &sessions, order.clone()).unwrap(), None);
+        assert!(resolve_index_in("32", &sessions, order).is_err());
+    }
+    
+    fn json_keys(value: &serde_json::Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value.as_object().unwrap().keys().map(|key| key.as_str()).collect();
+        keys.sort();
+        keys
+    }
+    
+    #[test]
+    fn json_listing_keeps_its_keys() {
+        let layout = r#"
+            layout {
+                tab name="code" {
+                    pane command="nvim" cwd="/src" focus=true
+                    pane cwd="/tmp"
+                }
+                tab name="shell" {
+                    pane cwd="/home/me"
+                }
+            }
+        "#;
+        let sessions_with_tabs = vec![
+            (session_with_hash("alpha", "aa"), parse_kdl_layout(layout)),
+            (session_with_hash("beta", "bb"), Err(anyhow!("session went away"))),
+        ];
+        let json: serde_json::Value = serde_json::from_str(&sessions_json(&sessions_with_tabs, false).unwrap()).unwrap();
+        let entries = json.as_array().unwrap();
+        
+        assert_eq!(json_keys(&entries[0]), [
+            "age_secs", "description", "hash_prefix", "is_current", "is_exited",
+            "name", "pinned", "shortest_prefix", "tabs", "tags",
+        ]);
+        assert_eq!(entries[0]["name"], "alpha");
+        assert_eq!(entries[0]["shortest_prefix"], "a");
+        
+        let tabs = entries[0]["tabs"].as_array().unwrap();
+        assert_eq!(json_keys(&tabs[0]), ["command", "cwd", "focused", "name", "pane_count", "panes"]);
+        assert_eq!(tabs[0]["command"], "nvim");
+        assert_eq!(tabs[0]["cwd"], "/src");
+        // A tab running just a shell still has the key, with nothing in it
+        assert!(tabs[1]["command"].is_null());
+        
+        // Tabs that couldn't be fetched are reported, not fatal
+        assert_eq!(entries[1]["tabs"], serde_json::json!([]));
+        assert_eq!(entries[1]["tabs_error"], "session went away");
+    }
+    
+    fn found<'a>(query: &str, sessions: &'a [SessionInfo]) -> Option<&'a str> {
+        find_session(query, sessions).unwrap().map(|s| s.name.as_str())
+    }
+    
+    #[test]
+    fn finds_sessions_by_name_prefix_and_substring() {
+        let sessions = [
+            session_with_hash("api", "a1"),
+            session_with_hash("api-old", "b2"),
+            session_with_hash("Frontend", "c3"),
+        ];
+        assert_eq!(found("api", &sessions), Some("api"));
+        assert_eq!(found("b", &sessions), Some("api-old"));
+        assert_eq!(found("old", &sessions), Some("api-old"));
+        assert_eq!(found("FRONT", &sessions), Some("Frontend"));
+        assert_eq!(found("nothing", &sessions), None);
+    }
+    
+    #[test]
+    fn ambiguous_substring_is_an_error() {
+        let sessions = [session_with_hash("web-api", "a1"), session_with_hash("web-ui", "b2")];
+        let error = find_session("web", &sessions).unwrap_err();
+        assert!(error.to_string().contains("Ambiguous session 'web'"), "{}", error);
+    }
+    
+    #[test]
+    fn here_matches_the_session_working_in_the_directory() {
+        let root = env::temp_dir().join(format!("z-test-here-{}", std::process::id()));
+        let project = root.join("project");
+        let other = root.join("other");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        let path = |dir: &Path| dir.to_string_lossy().to_string();
+        let tab = |cwds: &[Option<String>]| TabInfo {
+            name: "tab".to_string(),
+            panes: cwds.iter().map(|cwd| PaneInfo { command: None, cwd: cwd.clone(), floating: false }).collect(),
+            pane_count: cwds.len(),
+            focused: false,
+            command: None,
+            cwd: None,
+            branch: None,
+        };
+        
+        let sessions_with_tabs = vec![
+            (session_with_hash("broken", "aa"), Err(anyhow!("no layout"))),
+            // Relative cwds can't be compared, so they never match
+            (session_with_hash("relative", "bb"), Ok(vec![tab(&[Some("project".to_string())])])),
+            (session_with_hash("elsewhere", "cc"), Ok(vec![tab(&[Some(path(&other)), None])])),
+            (session_with_hash("working", "dd"), Ok(vec![tab(&[None]), tab(&[Some(path(&project.join(".")))])])),
+        ];
+        
+        let target = project.canonicalize().unwrap();
+        assert_eq!(session_working_in(&target, &sessions_with_tabs).as_deref(), Some("working"));
+        
+        let nowhere = root.canonicalize().unwrap();
+        assert_eq!(session_working_in(&nowhere, &sessions_with_tabs), None);
+        
+        fs::remove_dir_all(&root).unwrap();
+    }
+}